@@ -1,19 +1,36 @@
 #![allow(unused)]
 use crate::error::BuilderError;
+use crate::router::{Route, Router};
 use crate::HookListener;
-use std::{fmt::Display, io, net::TcpListener, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io,
+    net::TcpListener,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+/// Hub used when none is set
+const DEFAULT_HUB: &str = "https://pubsubhubbub.appspot.com";
+/// Topic template used when none is set: the feed of the YouTube channel `{id}`
+const DEFAULT_TOPIC: &str = "https://www.youtube.com/xml/feeds/videos.xml?channel_id={id}";
+const HUB_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Default)]
 pub struct HookListenerBuilder {
     listener: Option<TcpListener>,
     callback: Option<String>,
+    hub: Option<String>,
+    topic: Option<String>,
+    routes: Vec<Route>,
     new_only: bool,
+    secrets: HashMap<String, String>,
 }
 
 impl HookListenerBuilder {
     pub fn listener(mut self, address: impl Into<String>, port: u32) -> Result<Self, BuilderError> {
-        let bind = format!("{}:{}", address.into(), port);
-        self.listener = Some(TcpListener::bind(bind).map_err(BuilderError::CannotBind)?);
+        self.listener = Some(bind(address, port)?);
         Ok(self)
     }
 
@@ -22,16 +39,74 @@ impl HookListenerBuilder {
         self
     }
 
+    /// URL of the hub subscription requests are sent to, YouTube's hub by default.
+    pub fn hub(mut self, hub: impl Into<String>) -> Self {
+        self.hub = Some(hub.into());
+        self
+    }
+
+    /// Template of the topic URL, where `{id}` is replaced by the subscription id.
+    ///
+    /// Defaults to the feed of the YouTube channel `{id}`.
+    pub fn topic(mut self, topic: impl Into<String>) -> Self {
+        self.topic = Some(topic.into());
+        self
+    }
+
+    /// Callback routes, e.g. `/youtube/{id}`, relative to the callback URL.
+    ///
+    /// `{id}` is replaced by the subscription id in the callback URL sent to the hub, and
+    /// returned with the entries pushed on that route. The first route is the one used on
+    /// subscription; without any route, the callback URL is used as is.
+    pub fn routes(
+        mut self,
+        patterns: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Self, BuilderError> {
+        for pattern in patterns {
+            self.routes.push(Route::parse(pattern.as_ref())?);
+        }
+        Ok(self)
+    }
+
     pub fn new_only(mut self, new_only: bool) -> Self {
         self.new_only = new_only;
         self
     }
 
+    /// Secrets of the existing subscriptions, by subscription id.
+    pub fn secrets(mut self, secrets: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.secrets.extend(secrets);
+        self
+    }
+
     pub fn build(self) -> Result<HookListener, BuilderError> {
+        let hub = self.hub.unwrap_or_else(|| DEFAULT_HUB.to_string());
+        if !url::Url::parse(&hub).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+            return Err(BuilderError::InvalidHub(hub));
+        }
+        let client = reqwest::Client::builder().timeout(HUB_TIMEOUT).build()?;
+
         Ok(HookListener {
-            listener: Arc::new(self.listener.ok_or_else(|| BuilderError::MissingListener)?),
-            callback: self.callback.ok_or_else(|| BuilderError::MissingCallback)?,
+            listener: Mutex::new(self.listener.ok_or(BuilderError::MissingListener)?),
+            callback: self.callback.ok_or(BuilderError::MissingCallback)?,
+            hub,
+            topic: self.topic.unwrap_or_else(|| DEFAULT_TOPIC.to_string()),
+            client,
+            router: Arc::new(Router::new(self.routes)),
             new_only: self.new_only,
+            secrets: Arc::new(RwLock::new(self.secrets)),
+            pending: Arc::default(),
+            running: Mutex::new(None),
         })
     }
 }
+
+pub(super) fn bind(address: impl Into<String>, port: u32) -> Result<TcpListener, BuilderError> {
+    let bind = format!("{}:{}", address.into(), port);
+    let listener = TcpListener::bind(bind).map_err(BuilderError::CannotBind)?;
+    // Required to hand the listener over to tokio
+    listener
+        .set_nonblocking(true)
+        .map_err(BuilderError::CannotBind)?;
+    Ok(listener)
+}
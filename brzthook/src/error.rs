@@ -1,5 +1,3 @@
-use crate::prelude::Notification;
-
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Builder error")]
@@ -10,10 +8,18 @@ pub enum Error {
     Parse(#[from] ParseError),
     #[error("Subscription rejected")]
     SubscriptionError(String),
+    #[error("Hub responded with {status}: {reason}")]
+    Hub { status: u16, reason: String },
+    #[error("Request to the hub failed")]
+    Http(#[from] reqwest::Error),
     #[error("Error while handling connection")]
     HandleConnection(#[from] HandleConnectionError),
     #[error("Notfication error")]
     Notification(#[from] NotificationError),
+    #[error("Signature verification failed")]
+    Signature(#[from] SignatureError),
+    #[error("Listener is already running")]
+    AlreadyListening,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -24,6 +30,12 @@ pub enum BuilderError {
     MissingListener,
     #[error("Missing callback URL")]
     MissingCallback,
+    #[error("Invalid route {0}")]
+    InvalidRoute(String),
+    #[error("Invalid hub URL {0}")]
+    InvalidHub(String),
+    #[error("HTTP client error")]
+    Client(#[from] reqwest::Error),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -36,18 +48,22 @@ pub enum ParseError {
     ParameterError(String),
     #[error("Requested resource does not exists")]
     UriError,
+    #[error("Message is too large")]
+    TooLarge,
+    #[error("Invalid chunked body")]
+    ChunkError,
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum HandleConnectionError {
     #[error("Message is empty")]
     Empty,
+    #[error("Connection closed before the end of the message")]
+    Incomplete,
     #[error("Message has non-utf8 characters")]
     FormatUtf8Error(#[from] std::string::FromUtf8Error),
     #[error("Message has no body")]
     NoBodyError,
-    #[error("Send error")]
-    SendError(#[from] Box<std::sync::mpsc::SendError<Notification>>),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -56,4 +72,22 @@ pub enum NotificationError {
     MissingParameter(String),
     #[error("OffsetDateTime parse error")]
     DateTimeError(#[from] time::error::Parse),
+    #[error("Invalid XML")]
+    Xml(#[from] roxmltree::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SignatureError {
+    #[error("Missing X-Hub-Signature header")]
+    Missing,
+    #[error("No secret known for subscription {0}")]
+    UnknownSubscription(String),
+    #[error("Malformed signature: {0}")]
+    Malformed(String),
+    #[error("Unsupported signature method {0}")]
+    UnsupportedMethod(String),
+    #[error("Invalid HMAC key")]
+    InvalidKey,
+    #[error("Signature does not match the content")]
+    Mismatch,
 }
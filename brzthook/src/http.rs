@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use crate::error::ParseError;
+
+/// Maximum size of the request/status line and headers
+pub(super) const MAX_HEAD_SIZE: usize = 16 * 1024;
+/// Maximum size of a message body
+pub(super) const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// HTTP headers; names are case-insensitive and stored lowercase.
+#[derive(Debug, Default)]
+pub(super) struct Headers(HashMap<String, String>);
+
+impl Headers {
+    fn parse<'a>(lines: impl Iterator<Item = &'a str>) -> Result<Self, ParseError> {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        for line in lines {
+            let (key, value) = line
+                .split_once(':')
+                .filter(|(key, _)| !key.is_empty() && !key.ends_with(char::is_whitespace))
+                .ok_or_else(|| ParseError::HeaderError(line.to_string()))?;
+            let key = key.to_ascii_lowercase();
+            let value = value.trim();
+
+            // Repeated headers are combined in a comma separated list
+            headers
+                .entry(key)
+                .and_modify(|v| {
+                    v.push_str(", ");
+                    v.push_str(value);
+                })
+                .or_insert_with(|| value.to_string());
+        }
+
+        Ok(Self(headers))
+    }
+
+    /// Get the value of a header, ignoring the case of its name.
+    pub(super) fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+
+    fn content_length(&self) -> Result<Option<usize>, ParseError> {
+        self.get("Content-Length")
+            .map(|len| {
+                len.parse()
+                    .map_err(|_| ParseError::HeaderError(format!("Content-Length: {len}")))
+            })
+            .transpose()
+    }
+
+    fn is_chunked(&self) -> bool {
+        self.get("Transfer-Encoding").is_some_and(|encoding| {
+            encoding
+                .rsplit(',')
+                .next()
+                .is_some_and(|last| last.trim().eq_ignore_ascii_case("chunked"))
+        })
+    }
+}
+
+/// Head of a message: the start line and the headers.
+#[derive(Debug)]
+pub(super) struct Head<'a> {
+    pub(super) start_line: &'a str,
+    pub(super) headers: Headers,
+    /// Index of the first byte of the body in the buffer
+    pub(super) body_start: usize,
+}
+
+/// Parse the head of the message in `buf`.
+///
+/// Returns `None` if the end of the head has not been received yet.
+pub(super) fn parse_head(buf: &[u8]) -> Result<Option<Head<'_>>, ParseError> {
+    let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+        if buf.len() > MAX_HEAD_SIZE {
+            return Err(ParseError::TooLarge);
+        }
+        return Ok(None);
+    };
+
+    let head = std::str::from_utf8(&buf[..end])
+        .map_err(|_| ParseError::HeaderError("non-utf8 header".to_string()))?;
+    let mut lines = head.split("\r\n");
+    let start_line = lines
+        .next()
+        .filter(|line| !line.is_empty())
+        .ok_or_else(|| ParseError::NotFound("start line".to_string()))?;
+    let headers = Headers::parse(lines)?;
+
+    Ok(Some(Head {
+        start_line,
+        headers,
+        body_start: end + 4,
+    }))
+}
+
+/// How the length of the body is determined when neither `Content-Length` nor
+/// `Transfer-Encoding` are set.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Unframed {
+    /// The message has no body (requests)
+    Empty,
+    /// The body ends when the connection is closed (responses)
+    UntilEof { eof: bool },
+}
+
+/// Read the body following the head, according to `Transfer-Encoding` and
+/// `Content-Length` headers.
+///
+/// Returns `None` if the body has not been entirely received yet.
+pub(super) fn parse_body(
+    headers: &Headers,
+    buf: &[u8],
+    unframed: Unframed,
+) -> Result<Option<Vec<u8>>, ParseError> {
+    if headers.is_chunked() {
+        return decode_chunked(buf);
+    }
+
+    match (headers.content_length()?, unframed) {
+        (Some(len), _) if len > MAX_BODY_SIZE => Err(ParseError::TooLarge),
+        (Some(len), _) if buf.len() < len => Ok(None),
+        (Some(len), _) => Ok(Some(buf[..len].to_vec())),
+        (None, Unframed::Empty) => Ok(Some(vec![])),
+        (None, Unframed::UntilEof { eof: true }) => Ok(Some(buf.to_vec())),
+        (None, Unframed::UntilEof { eof: false }) if buf.len() > MAX_BODY_SIZE => {
+            Err(ParseError::TooLarge)
+        }
+        (None, Unframed::UntilEof { eof: false }) => Ok(None),
+    }
+}
+
+/// Decode a body sent with `Transfer-Encoding: chunked`.
+///
+/// Chunk extensions and trailers are ignored.
+fn decode_chunked(mut buf: &[u8]) -> Result<Option<Vec<u8>>, ParseError> {
+    let mut body = vec![];
+
+    loop {
+        let Some(line_end) = find_crlf(buf) else {
+            return Ok(None);
+        };
+        let size_line =
+            std::str::from_utf8(&buf[..line_end]).map_err(|_| ParseError::ChunkError)?;
+        let size = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| ParseError::ChunkError)?;
+        buf = &buf[line_end + 2..];
+
+        if size == 0 {
+            // Skip the trailers until the final empty line
+            loop {
+                let Some(line_end) = find_crlf(buf) else {
+                    return Ok(None);
+                };
+                buf = &buf[line_end + 2..];
+                if line_end == 0 {
+                    return Ok(Some(body));
+                }
+            }
+        }
+
+        if body.len() + size > MAX_BODY_SIZE {
+            return Err(ParseError::TooLarge);
+        }
+        if buf.len() < size + 2 {
+            return Ok(None);
+        }
+        if &buf[size..size + 2] != b"\r\n" {
+            return Err(ParseError::ChunkError);
+        }
+        body.extend_from_slice(&buf[..size]);
+        buf = &buf[size + 2..];
+    }
+}
+
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|w| w == b"\r\n")
+}
+
+/// Parse a percent-encoded query string.
+pub(super) fn parse_query(query: &str) -> HashMap<String, String> {
+    url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_names_are_case_insensitive() {
+        let buf = b"POST / HTTP/1.1\r\ncontent-length: 0\r\nX-HUB-SIGNATURE: sha1=00\r\n\r\n";
+        let head = parse_head(buf).unwrap().unwrap();
+
+        assert_eq!(head.headers.get("Content-Length"), Some("0"));
+        assert_eq!(head.headers.get("X-Hub-Signature"), Some("sha1=00"));
+        assert_eq!(head.body_start, buf.len());
+    }
+
+    #[test]
+    fn incomplete_head() {
+        assert!(parse_head(b"GET / HTTP/1.1\r\nHost: exa")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn malformed_header() {
+        let buf = b"GET / HTTP/1.1\r\nthis is not a header\r\n\r\n";
+        assert!(matches!(parse_head(buf), Err(ParseError::HeaderError(_))));
+    }
+
+    #[test]
+    fn content_length_body() {
+        let head = parse_head(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        let headers = head.headers;
+
+        assert_eq!(parse_body(&headers, b"hel", Unframed::Empty).unwrap(), None);
+        assert_eq!(
+            parse_body(&headers, b"hello", Unframed::Empty).unwrap(),
+            Some(b"hello".to_vec())
+        );
+    }
+
+    #[test]
+    fn chunked_body() {
+        let head = parse_head(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        let headers = head.headers;
+        let body = b"7\r\n<feed> \r\n9;ext=1\r\n</feed>\r\n\r\n0\r\n\r\n";
+
+        assert_eq!(
+            parse_body(&headers, body, Unframed::Empty).unwrap(),
+            Some(b"<feed> </feed>\r\n".to_vec())
+        );
+        assert_eq!(
+            parse_body(&headers, &body[..body.len() - 2], Unframed::Empty).unwrap(),
+            None
+        );
+        assert!(matches!(
+            parse_body(&headers, b"zz\r\n", Unframed::Empty),
+            Err(ParseError::ChunkError)
+        ));
+    }
+
+    #[test]
+    fn query_is_percent_decoded() {
+        let params = parse_query("hub.topic=https%3A%2F%2Fwww.youtube.com%2Fxml%2Ffeeds%2Fvideos.xml%3Fchannel_id%3DUC123&hub.challenge=a+b%2Bc");
+
+        assert_eq!(
+            params.get("hub.topic").map(String::as_str),
+            Some("https://www.youtube.com/xml/feeds/videos.xml?channel_id=UC123")
+        );
+        assert_eq!(
+            params.get("hub.challenge").map(String::as_str),
+            Some("a b+c")
+        );
+    }
+}
@@ -1,18 +1,23 @@
 mod buidler;
 mod error;
+mod http;
 mod message;
 mod notification;
 mod parse;
 pub mod prelude;
 mod request;
 mod response;
+mod router;
+mod signature;
 
 use std::{
+    collections::{HashMap, HashSet},
     fmt,
     fs::File,
-    io::{prelude::*, BufReader},
-    net::{TcpListener, TcpStream},
-    sync::{mpsc::Sender, Arc},
+    io::prelude::*,
+    net::{SocketAddr, TcpListener},
+    str::FromStr,
+    sync::{Arc, Mutex, RwLock},
     time::Duration,
 };
 
@@ -20,17 +25,62 @@ use message::Message;
 use prelude::*;
 use request::Request;
 use response::Response;
+use router::Router;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::{
+        mpsc::{self, Receiver},
+        oneshot,
+    },
+    task::{JoinHandle, JoinSet},
+};
 use tracing::{debug, error, info, warn};
 
-use crate::buidler::HookListenerBuilder;
-use crate::error::{Error::SubscriptionError, HandleConnectionError, ParseError};
+use crate::buidler::{bind, HookListenerBuilder};
+use crate::error::{Error::SubscriptionError, HandleConnectionError, ParseError, SignatureError};
+
+/// Secrets given to the hub, by subscription id
+type Secrets = Arc<RwLock<HashMap<String, String>>>;
+
+/// Requests sent to the hub and waiting for their verification of intent, by topic url
+type Pending = Arc<Mutex<HashSet<(String, Mode)>>>;
+
+/// Accept loop started by [`HookListener::listen`]
+#[derive(Debug)]
+struct Running {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+/// Configuration and state shared by the connection handlers
+#[derive(Debug)]
+struct Shared {
+    new_only: bool,
+    topic: String,
+    router: Arc<Router>,
+    secrets: Secrets,
+    pending: Pending,
+}
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Mode {
     Subscribe,
     Unsubscribe,
 }
 
+impl FromStr for Mode {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "subscribe" => Ok(Self::Subscribe),
+            "unsubscribe" => Ok(Self::Unsubscribe),
+            _ => Err(ParseError::ParameterError(format!("Invalid hub.mode {s}"))),
+        }
+    }
+}
+
 impl fmt::Display for Mode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -46,9 +96,16 @@ impl fmt::Display for Mode {
 
 #[derive(Debug)]
 pub struct HookListener {
-    pub listener: Arc<TcpListener>,
+    listener: Mutex<TcpListener>,
     pub callback: String,
+    pub hub: String,
+    pub topic: String,
     pub new_only: bool,
+    router: Arc<Router>,
+    client: reqwest::Client,
+    secrets: Secrets,
+    pending: Pending,
+    running: Mutex<Option<Running>>,
 }
 
 impl HookListener {
@@ -57,52 +114,188 @@ impl HookListener {
     }
 
     /// Start listening for incoming streams.
-    pub fn listen(&self, sender: &Sender<Result<Notification, Error>>) {
+    ///
+    /// The accept loop is spawned on the current tokio runtime, and each connection is
+    /// handled in its own task. The results of the verifications of intent, each entry of
+    /// the pushed feeds and the errors are sent to the returned `Receiver`; the loop stops
+    /// when the receiver is dropped.
+    ///
+    /// Stop it with [`HookListener::shutdown`]; it can then be started again.
+    ///
+    /// # Errors:
+    ///
+    /// The listener is already running.
+    ///
+    /// The TCP listener cannot be registered in the tokio runtime.
+    ///
+    /// # Panics:
+    ///
+    /// Called outside of a tokio runtime.
+    pub fn listen(&self) -> Result<Receiver<Result<Event, Error>>, Error> {
+        let mut running = self.running.lock().expect("running lock poisoned");
+        if running.as_ref().is_some_and(|r| !r.task.is_finished()) {
+            return Err(Error::AlreadyListening);
+        }
+
         info!("Start listening.");
 
-        let listener = Arc::clone(&self.listener);
-        let sender = sender.clone();
-        let new_only = self.new_only;
+        let listener = self.listener.lock().expect("listener lock poisoned");
+        let listener = tokio::net::TcpListener::from_std(listener.try_clone()?)?;
+        let (stop, mut stopped) = oneshot::channel();
+        let (sender, receiver) = mpsc::channel(CHANNEL_SIZE);
+        let shared = Arc::new(Shared {
+            new_only: self.new_only,
+            topic: self.topic.clone(),
+            router: Arc::clone(&self.router),
+            secrets: Arc::clone(&self.secrets),
+            pending: Arc::clone(&self.pending),
+        });
+
+        let task = tokio::spawn(async move {
+            let mut connections = JoinSet::new();
+            loop {
+                // Stops when asked to, or when the listener or the receiver is dropped
+                let stream = tokio::select! {
+                    _ = &mut stopped => break,
+                    () = sender.closed() => break,
+                    Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+                    stream = listener.accept() => stream,
+                };
 
-        std::thread::spawn(move || {
-            for stream in listener.incoming() {
                 match stream {
-                    Ok(stream) => match handle_connection(stream, new_only) {
-                        Ok(reponse) => {
-                            if let Some(notification) = reponse {
-                                info!("Sending new notification");
-                                sender.send(Ok(notification)).unwrap();
+                    Ok((stream, addr)) => {
+                        debug!("Incoming connection from {addr}");
+                        let sender = sender.clone();
+                        let shared = Arc::clone(&shared);
+                        connections.spawn(async move {
+                            let messages = match handle_connection(stream, &shared).await {
+                                Ok(events) => events.into_iter().map(Ok).collect(),
+                                Err(e) => vec![Err(e)],
+                            };
+                            for message in messages {
+                                info!("Sending new event");
+                                if sender.send(message).await.is_err() {
+                                    warn!("Receiver dropped; event lost");
+                                    break;
+                                }
                             }
+                        });
+                    }
+                    Err(e) => {
+                        if sender.send(Err(Error::TcpError(e))).await.is_err() {
+                            break;
                         }
-                        Err(e) => sender.send(Err(e)).unwrap(),
-                    },
-                    Err(e) => sender.send(Err(Error::TcpError(e))).unwrap(),
+                    }
                 }
             }
+
+            // Stop accepting connections, and let the ones in flight finish
+            drop(listener);
+            debug!("Draining {} connections", connections.len());
+            while connections.join_next().await.is_some() {}
+            info!("Stop listening.");
         });
+        *running = Some(Running { stop, task });
+
+        Ok(receiver)
+    }
+
+    /// Stop the listener started by [`HookListener::listen`].
+    ///
+    /// No new connection is accepted, and it returns once the connections in flight
+    /// have been handled. The socket stays bound until the `HookListener` is dropped or
+    /// rebound.
+    pub async fn shutdown(&self) {
+        let running = self.running.lock().expect("running lock poisoned").take();
+        let Some(Running { stop, task }) = running else {
+            return;
+        };
+
+        info!("Shutting down listener.");
+        // The loop may already have stopped with its receiver
+        let _ = stop.send(());
+        if let Err(e) = task.await {
+            error!("Listener task failed: {e}");
+        }
+    }
+
+    /// Stop the listener and bind its socket to a new address, e.g. after a configuration
+    /// reload; call [`HookListener::listen`] to start it again.
+    pub async fn rebind(&self, address: impl Into<String>, port: u32) -> Result<(), Error> {
+        self.shutdown().await;
+
+        let listener = bind(address, port)?;
+        *self.listener.lock().expect("listener lock poisoned") = listener;
+        info!("Listener bound to {}", self.local_addr()?);
+
+        Ok(())
+    }
+
+    /// Address the listener is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self
+            .listener
+            .lock()
+            .expect("listener lock poisoned")
+            .local_addr()?)
+    }
+
+    /// Callback URL given to the hub for the subscription `id`.
+    pub fn callback_url(&self, id: impl AsRef<str>) -> String {
+        self.router.callback_url(&self.callback, id.as_ref())
+    }
+
+    /// Topic URL of the subscription `id`.
+    pub fn topic_url(&self, id: impl AsRef<str>) -> String {
+        self.topic.replace("{id}", id.as_ref())
+    }
+
+    /// Get the secret used to sign the content distributed for the subscription `id`.
+    pub fn secret(&self, id: impl AsRef<str>) -> Option<String> {
+        self.secrets
+            .read()
+            .expect("secrets lock poisoned")
+            .get(id.as_ref())
+            .cloned()
+    }
+
+    /// Register the secret of an existing subscription, e.g. loaded from storage on startup.
+    pub fn set_secret(&self, id: impl Into<String>, secret: impl Into<String>) {
+        self.secrets
+            .write()
+            .expect("secrets lock poisoned")
+            .insert(id.into(), secret.into());
     }
 
     /// Send a subscription/unsubscription request to the hub.
     ///
-    /// It sends a POST request to the hub with the formatted topic url
-    /// , the callback url and the subscription mode.
+    /// It sends a POST request to the hub with the topic url, the callback url and the
+    /// subscription mode; `{id}` is replaced by `id` in the topic template and in the
+    /// callback route.
     ///
-    /// # Panics:
+    /// On subscription, the secret of the subscription is sent as `hub.secret`; a new
+    /// one is generated if none is known yet. Get it with [`HookListener::secret`] to
+    /// persist it.
+    ///
+    /// The listener only answers the verification of intent, or the denial, of a
+    /// request sent here; any other is answered with `404 Not Found`.
     ///
-    /// Subscription mode is not "subscribe" or "unsubscribe".
+    /// Returns the status code of the hub response, `202 Accepted` once the request is
+    /// queued for verification of intent.
     ///
-    /// Publisher configuration is not found.
+    /// # Errors:
     ///
-    /// Request can not be streamed to the hub address.
-    pub fn subscribe(&self, id: impl AsRef<str>, mode: Mode) -> Result<(), Error> {
+    /// The request cannot be sent to the hub.
+    ///
+    /// The hub answers with a 4xx or 5xx status code.
+    pub async fn subscribe(&self, id: impl AsRef<str>, mode: Mode) -> Result<u16, Error> {
         let id = id.as_ref();
 
         info!("Initiating {mode} request with id: {id}");
 
-        //TODO: not hardcode this
-        let topic_url = format!("https://www.youtube.com/xml/feeds/videos.xml?channel_id={id}");
-        let hub = "https://pubsubhubbub.appspot.com";
-        let callback_url = &self.callback;
+        let topic_url = self.topic_url(id);
+        let hub = &self.hub;
+        let callback_url = self.callback_url(id);
 
         info!(
             r#"
@@ -114,142 +307,255 @@ hub.topic={}"#,
         );
 
         // Building the subscription request
-        let body = format!("hub.callback={callback_url}&hub.mode={mode}&hub.topic={topic_url}");
-        let len = body.as_bytes().len();
-        let post_request = format!("POST / HTTP/1.1\r\nHost: pubsubhubbub.appspot.com\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {len}\r\n\r\n{body}");
-        debug!("{post_request:?}");
-
-        // Connect a socket and send the request
-        let hub_addr = format!(
-            "{}:80",
-            hub.trim_start_matches("https://")
-                .trim_start_matches("https://"),
-        );
-        let mut stream = TcpStream::connect(hub_addr)?;
-        stream.write_all(post_request.as_bytes())?;
-        stream.flush()?;
+        let mut params = vec![
+            ("hub.callback", callback_url),
+            ("hub.mode", mode.to_string()),
+            ("hub.topic", topic_url.clone()),
+        ];
+        if let Mode::Subscribe = mode {
+            let secret = self
+                .secrets
+                .write()
+                .expect("secrets lock poisoned")
+                .entry(id.to_string())
+                .or_insert_with(signature::generate_secret)
+                .clone();
+            params.push(("hub.secret", secret));
+        }
 
-        Ok(())
+        // The hub may verify the request before answering it
+        let request = (topic_url, mode);
+        self.pending
+            .lock()
+            .expect("pending lock poisoned")
+            .insert(request.clone());
+        let forget = || {
+            self.pending
+                .lock()
+                .expect("pending lock poisoned")
+                .remove(&request);
+        };
+
+        let response = match self.client.post(hub).form(&params).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                forget();
+                return Err(e.into());
+            }
+        };
+        let status = response.status();
+        debug!("Hub response: {status}");
+
+        if status.is_client_error() || status.is_server_error() {
+            forget();
+            let reason = response.text().await.unwrap_or_default();
+            return Err(Error::Hub {
+                status: status.as_u16(),
+                reason: reason.trim().to_string(),
+            });
+        }
+
+        Ok(status.as_u16())
     }
 }
 
 const BUF_SIZE: usize = 1024;
+const CHANNEL_SIZE: usize = 32;
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
 
-fn handle_connection(mut stream: TcpStream, new_only: bool) -> Result<Option<Notification>, Error> {
-    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
-    let mut buf_reader = BufReader::new(&mut stream);
-
-    let mut n_bytes = 0;
+async fn handle_connection(mut stream: TcpStream, shared: &Shared) -> Result<Vec<Event>, Error> {
     let mut received = vec![];
-    loop {
+    let message = loop {
         let mut buf = [0; BUF_SIZE];
-        match buf_reader.read(&mut buf) {
-            Ok(n) => {
-                n_bytes += n;
-                received.extend_from_slice(&buf);
-                if n < BUF_SIZE {
-                    break;
-                }
-            }
+        let read = tokio::time::timeout(READ_TIMEOUT, stream.read(&mut buf))
+            .await
+            .unwrap_or_else(|_| Err(std::io::ErrorKind::TimedOut.into()));
+        let n = match read {
+            Ok(n) => n,
             Err(e) => {
                 error!("Error reading buffer: {e}");
-                stream.flush()?;
+                stream.flush().await?;
                 return Err(Error::TcpError(e));
             }
+        };
+        received.extend_from_slice(&buf[..n]);
+
+        // Read until the message is complete, as framed by its headers
+        let eof = n == 0;
+        match Message::parse(&received, eof)? {
+            Some(message) => break message,
+            None if eof && received.is_empty() => return Err(HandleConnectionError::Empty.into()),
+            None if eof => return Err(HandleConnectionError::Incomplete.into()),
+            None => {}
         }
-    }
-    info!("Received {n_bytes} bytes");
-
-    received.retain(|&b| b != 0);
-    let message = String::from_utf8(received).map_err(HandleConnectionError::FormatUtf8Error)?;
-    let message = Message::from_str(&message)?;
+    };
+    info!("Received {} bytes", received.len());
 
     debug!("Message:\n{message:#?}");
 
-    let notification = match message {
-        Message::Request(request) => handle_request(request, stream, new_only)?,
+    let events = match message {
+        Message::Request(request) => handle_request(request, &mut stream, shared).await?,
         Message::Response(response) => {
             handle_response(response)?;
-            None
+            vec![]
         }
     };
 
-    debug!("End of handle_connection: {notification:#?}");
-    Ok(notification)
+    debug!("End of handle_connection: {events:#?}");
+    Ok(events)
 }
 
-fn handle_request(
+async fn handle_request(
     request: Request,
-    mut stream: TcpStream,
-    new_only: bool,
-) -> Result<Option<Notification>, Error> {
-    let from = request
-        .headers
-        .get("From")
-        .ok_or_else(|| ParseError::NotFound("From header".to_string()))?;
-    if *from != "googlebot(at)googlebot.com" {
-        error!("unknown source : {from}");
-        return Err(HandleConnectionError::Empty.into());
-    }
-
-    let method = request.request_line.method;
-    let _path = request.request_line.path;
+    stream: &mut TcpStream,
+    shared: &Shared,
+) -> Result<Vec<Event>, Error> {
+    let method = request.request_line.method.as_str();
+
+    let Some(route) = shared.router.matches(&request.request_line.path) else {
+        let response = response::not_found();
+        info!("Sending: {response:?}");
+        stream.write_all(response.as_bytes()).await?;
+        stream.flush().await?;
+        return Err(ParseError::UriError.into());
+    };
 
-    let notification = match method {
+    let events = match method {
         // The hub send a GET request for the verification of intent
         // The scubscriber must answer with a 2XX status code and echo the hub.challenge value
+        // The hub may also send a GET request to tell the subscription has been denied
         "GET" => {
             info!("Received GET request");
-            let params = request
-                .request_line
-                .params
-                .ok_or_else(|| ParseError::ParameterError("No paramater in request".to_string()))?;
-
-            if let Some(reason) = params.get("hub.reason") {
-                return Err(SubscriptionError((*reason).to_string()));
+            let params =
+                request.request_line.params.as_ref().ok_or_else(|| {
+                    ParseError::ParameterError("No paramater in request".to_string())
+                })?;
+            let param = |name: &str| {
+                params
+                    .get(name)
+                    .ok_or_else(|| ParseError::NotFound(name.to_string()))
+            };
+
+            let topic = param("hub.topic")?.clone();
+            let id = route
+                .id()
+                .map(ToString::to_string)
+                .or_else(|| router::topic_id(&shared.topic, &topic));
+
+            // A denial answers a subscription request
+            let (mode, response, event) = if param("hub.mode")? == "denied" {
+                let reason = params.get("hub.reason").cloned().unwrap_or_default();
+                info!("Subscription to {topic} denied: {reason}");
+                let event = Event::Denied {
+                    id,
+                    topic: topic.clone(),
+                    reason,
+                };
+                (Mode::Subscribe, response::ok(""), event)
+            } else {
+                let mode = param("hub.mode")?.parse()?;
+                let lease_seconds = params
+                    .get("hub.lease_seconds")
+                    .map(|lease| {
+                        lease.parse().map_err(|_| {
+                            ParseError::ParameterError(format!("Invalid hub.lease_seconds {lease}"))
+                        })
+                    })
+                    .transpose()?;
+                let challenge = param("hub.challenge")?;
+                let event = Event::Verified {
+                    id,
+                    topic: topic.clone(),
+                    mode,
+                    lease_seconds,
+                };
+                (mode, response::ok(challenge), event)
+            };
+
+            // Anyone knowing the callback can send a GET request; only the answers to the
+            // requests sent by `subscribe` are from the hub
+            let requested = shared
+                .pending
+                .lock()
+                .expect("pending lock poisoned")
+                .remove(&(topic.clone(), mode));
+            if !requested {
+                warn!("No {mode} request pending for {topic}; pass");
+                let response = response::not_found();
+                info!("Sending: {response:?}");
+                stream.write_all(response.as_bytes()).await?;
+                stream.flush().await?;
+                return Ok(vec![]);
             }
 
-            let challenge = params
-                .get("hub.challenge")
-                .ok_or_else(|| ParseError::NotFound("hub.challenge".to_string()))?;
-            let response = format!("HTTP/1.1 200 OK\r\n\r\n{challenge}");
             info!("Sending: {response:?}");
-            stream.write_all(response.as_bytes())?;
+            stream.write_all(response.as_bytes()).await?;
 
-            None
+            vec![event]
         }
 
         // Request when a new resource is published
         "POST" => {
             info!("Received POST request");
 
-            let response = "HTTP/1.1 200 OK\r\n\r\n";
-            info!("Sending: {response}");
-            stream.write_all(response.as_bytes())?;
-
-            let notification = Notification::try_parse(
-                request
-                    .body
-                    .ok_or_else(|| HandleConnectionError::NoBodyError)?,
-            )?;
+            let response = response::ok("");
+            info!("Sending: {response:?}");
+            stream.write_all(response.as_bytes()).await?;
 
-            if new_only && !notification.is_new() {
-                info!("It's an updated video; pass");
-                None
-            } else {
-                Some(notification)
+            if request.body.is_empty() {
+                return Err(HandleConnectionError::NoBodyError.into());
             }
+            let body = String::from_utf8(request.body.clone())
+                .map_err(HandleConnectionError::FormatUtf8Error)?;
+            let mut events = Event::parse_feed(&body)?;
+
+            // The hub signs the content with the secret of the subscription;
+            // anything that does not match is not from the hub and is dropped.
+            // The subscription is given by the route, or else by the entries since a
+            // feed holds the entries of a single topic.
+            let Some(id) = route
+                .id()
+                .or_else(|| events.first().and_then(Event::channel_id))
+            else {
+                info!("Feed has no entry; pass");
+                return Ok(vec![]);
+            };
+            let secret = shared
+                .secrets
+                .read()
+                .expect("secrets lock poisoned")
+                .get(id)
+                .cloned()
+                .ok_or_else(|| SignatureError::UnknownSubscription(id.to_string()))?;
+            let signature = request
+                .header("X-Hub-Signature")
+                .ok_or(SignatureError::Missing)?;
+            signature::verify(&secret, &request.body, signature)?;
+
+            for event in &mut events {
+                event.set_route(route.clone());
+            }
+            events
+                .into_iter()
+                .filter(|event| match event {
+                    Event::Published(notification) if shared.new_only && !notification.is_new() => {
+                        info!("It's an updated video; pass");
+                        false
+                    }
+                    _ => true,
+                })
+                .collect()
         }
         _ => {
             warn!("Unhandled request: {request:#?}");
 
-            None
+            vec![]
         }
     };
 
-    stream.flush()?;
+    stream.flush().await?;
 
-    Ok(notification)
+    Ok(events)
 }
 
 fn handle_response(response: Response) -> Result<(), Error> {
@@ -257,22 +563,23 @@ fn handle_response(response: Response) -> Result<(), Error> {
         .headers
         .get("From")
         .ok_or_else(|| ParseError::NotFound("From header".to_string()))?;
-    if *from != "googlebot(at)googlebot.com" {
+    if from != "googlebot(at)googlebot.com" {
         error!("unknown source : {from}");
         return Err(HandleConnectionError::Empty.into());
     }
-    let status_code = response.status_line.status_code;
-    let _status_message = response.status_line.status_message;
+    let status_code = response.status_line.status_code.as_str();
+    let _status_message = &response.status_line.status_message;
 
     match status_code {
         "202" => {
             info!("Request accepted")
         }
         code if code.starts_with('4') || code.starts_with('5') => {
-            let reason = response
-                .body
-                .ok_or_else(|| HandleConnectionError::NoBodyError)?;
-            return Err(SubscriptionError(reason.to_string()));
+            if response.body.is_empty() {
+                return Err(HandleConnectionError::NoBodyError.into());
+            }
+            let reason = String::from_utf8_lossy(&response.body);
+            return Err(SubscriptionError(reason.into_owned()));
         }
         _ => {
             warn!("Unhandled response: {response:#?}");
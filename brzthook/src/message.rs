@@ -1,18 +1,21 @@
-use crate::{prelude::Error, request::Request, response::Response};
+use crate::{error::ParseError, request::Request, response::Response};
 
 #[derive(Debug)]
-pub enum Message<'a> {
-    Request(Request<'a>),
-    Response(Response<'a>),
+pub enum Message {
+    Request(Request),
+    Response(Response),
 }
 
-impl<'a> Message<'a> {
-    pub(super) fn from_str(message: &'a str) -> Result<Self, Error> {
-        if message.starts_with("HTTP/") {
-            Ok(Self::Response(Response::parse(message)?))
+impl Message {
+    /// Parse a message from the bytes received so far; `eof` tells whether the peer
+    /// closed the connection.
+    ///
+    /// Returns `None` if more bytes are needed.
+    pub(super) fn parse(buf: &[u8], eof: bool) -> Result<Option<Self>, ParseError> {
+        if buf.starts_with(b"HTTP/") {
+            Ok(Response::parse(buf, eof)?.map(Self::Response))
         } else {
-            Ok(Self::Request(Request::try_parse(message)?))
+            Ok(Request::try_parse(buf)?.map(Self::Request))
         }
     }
 }
-
@@ -1,60 +1,75 @@
-use time::{format_description::well_known::Iso8601, Duration, OffsetDateTime};
+use time::{Duration, OffsetDateTime};
 
-use crate::error::NotificationError;
-use crate::prelude::Error;
+use crate::prelude::{Error, Mode, RouteMatch};
+
+/// Request received from the hub.
+///
+/// `id` is the subscription id, given by the callback route or else found from the topic
+/// URL.
+#[derive(Debug)]
+pub enum Event {
+    /// The hub verified the intent of a (un)subscription; a subscription expires after
+    /// `lease_seconds` if given
+    Verified {
+        id: Option<String>,
+        topic: String,
+        mode: Mode,
+        lease_seconds: Option<u64>,
+    },
+    /// The hub, or the publisher, denied a subscription
+    Denied {
+        id: Option<String>,
+        topic: String,
+        reason: String,
+    },
+    /// A video has been published or updated
+    Published(Notification),
+    /// A video has been deleted or made private
+    Deleted(DeletedEntry),
+}
+
+impl Event {
+    /// Parse every entry of the Atom feed sent on content distribution, or of the RSS
+    /// feed of a channel.
+    pub fn parse_feed(xml: &str) -> Result<Vec<Self>, Error> {
+        Ok(super::parse::parse_feed(xml)?)
+    }
+
+    /// Set the callback route the entry was pushed on.
+    pub(super) fn set_route(&mut self, route: RouteMatch) {
+        match self {
+            Self::Published(notification) => notification.route = route,
+            Self::Deleted(deleted) => deleted.route = route,
+            Self::Verified { .. } | Self::Denied { .. } => {}
+        }
+    }
+
+    /// Id of the channel the entry belongs to.
+    pub fn channel_id(&self) -> Option<&str> {
+        match self {
+            Self::Published(notification) => Some(&notification.channel_id),
+            Self::Deleted(deleted) => Some(&deleted.channel_id),
+            Self::Verified { .. } | Self::Denied { .. } => None,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Notification {
     pub video_id: String,
     pub channel_id: String,
     pub video_title: String,
+    pub video_url: String,
     pub channel_name: String,
+    pub channel_url: String,
     pub published: OffsetDateTime,
     pub updated: OffsetDateTime,
+    /// Callback route the entry was pushed on
+    pub route: RouteMatch,
     pub raw: String,
 }
 
 impl Notification {
-    pub fn try_parse(xml: &str) -> Result<Self, Error> {
-        let parsed = super::parse::parse_xml(xml);
-
-        let video = Notification {
-            video_id: (*parsed
-                .get("yt:videoId")
-                .ok_or_else(|| NotificationError::MissingParameter("yt:videoId".to_string()))?)
-            .to_string(),
-            channel_id: (*parsed
-                .get("yt:channelId")
-                .ok_or_else(|| NotificationError::MissingParameter("yt:channelId".to_string()))?)
-            .to_string(),
-            video_title: (*parsed
-                .get("title")
-                .ok_or_else(|| NotificationError::MissingParameter("title".to_string()))?)
-            .to_string(),
-            channel_name: (*parsed
-                .get("name")
-                .ok_or_else(|| NotificationError::MissingParameter("name".to_string()))?)
-            .to_string(),
-            published: OffsetDateTime::parse(
-                parsed
-                    .get("published")
-                    .ok_or_else(|| NotificationError::MissingParameter("published".to_string()))?,
-                &Iso8601::DEFAULT,
-            )
-            .map_err(NotificationError::DateTimeError)?,
-            updated: OffsetDateTime::parse(
-                parsed
-                    .get("updated")
-                    .ok_or_else(|| NotificationError::MissingParameter("updated".to_string()))?,
-                &Iso8601::DEFAULT,
-            )
-            .map_err(NotificationError::DateTimeError)?,
-            raw: xml.to_string(),
-        };
-
-        Ok(video)
-    }
-
     pub fn is_new(&self) -> bool {
         self.updated - self.published < Duration::minutes(5)
     }
@@ -69,3 +84,27 @@ impl std::fmt::Display for Notification {
         writeln!(f, "{text}")
     }
 }
+
+/// Tombstone (`<at:deleted-entry>`) sent when a video is removed.
+#[derive(Debug)]
+pub struct DeletedEntry {
+    pub video_id: String,
+    pub channel_id: String,
+    pub video_url: String,
+    pub channel_name: String,
+    pub channel_url: String,
+    pub deleted: OffsetDateTime,
+    /// Callback route the entry was pushed on
+    pub route: RouteMatch,
+    pub raw: String,
+}
+
+impl std::fmt::Display for DeletedEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = format!(
+            "Deleted: {}\nAuthor: {}\n#id: {}; channel: {}",
+            self.video_url, self.channel_name, self.video_id, self.channel_id
+        );
+        writeln!(f, "{text}")
+    }
+}
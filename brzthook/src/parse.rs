@@ -1,36 +1,278 @@
-use std::collections::HashMap;
-
-pub(super) fn parse_xml(xml: &str) -> HashMap<&str, &str> {
-    let mut v = HashMap::new();
-    let tags = &[
-        "<yt:videoId>",
-        "<yt:channelId>",
-        "<title>",
-        "<name>",
-        "<published>",
-        "<updated>",
-    ];
-    let mut lines = xml.lines().map(str::trim);
-
-    for &t in tags {
-        let l = lines.find(|l| l.starts_with(t));
-        if let Some(l) = l {
-            // length of tag
-            let tl = t.len();
-            // length of line
-            let ll = l.len();
-
-            // <tag> without brackets
-            let t = &t[1..(tl - 1)];
-            // <tag>value</tag>
-            // value starts at index = tag.len() (here 4)
-            // value ends at index = tag.len - line.len - 1 (-1 because </tag> is one char longer
-            // than <tag>)
-            let l = &l[tl..(ll - tl - 1)];
-
-            v.insert(t, l);
+use roxmltree::{Document, Node};
+use time::{format_description::well_known::Iso8601, OffsetDateTime};
+
+use crate::error::NotificationError;
+use crate::notification::{DeletedEntry, Event, Notification};
+use crate::router::RouteMatch;
+
+const ATOM: &str = "http://www.w3.org/2005/Atom";
+const YT: &str = "http://www.youtube.com/xml/schemas/2015";
+const TOMBSTONES: &str = "http://purl.org/atompub/tombstones/1.0";
+
+/// Parse an Atom feed pushed by the hub, or the RSS feed of a channel which has the
+/// same entries.
+///
+/// Every `<entry>` gives a [`Event::Published`] and every `<at:deleted-entry>` a
+/// [`Event::Deleted`], in document order.
+pub(super) fn parse_feed(xml: &str) -> Result<Vec<Event>, NotificationError> {
+    let doc = Document::parse(xml)?;
+    let feed = doc.root_element();
+    if !feed.has_tag_name((ATOM, "feed")) {
+        return Err(NotificationError::MissingParameter("feed".to_string()));
+    }
+
+    let mut events = vec![];
+    for node in feed.children().filter(Node::is_element) {
+        if node.has_tag_name((ATOM, "entry")) {
+            events.push(Event::Published(parse_entry(node, xml)?));
+        } else if node.has_tag_name((TOMBSTONES, "deleted-entry")) {
+            events.push(Event::Deleted(parse_deleted_entry(node, xml)?));
         }
     }
 
-    v
+    Ok(events)
+}
+
+fn parse_entry(entry: Node, xml: &str) -> Result<Notification, NotificationError> {
+    let author = child(entry, ATOM, "author")?;
+
+    Ok(Notification {
+        video_id: text(entry, YT, "videoId")?,
+        channel_id: text(entry, YT, "channelId")?,
+        video_title: text(entry, ATOM, "title")?,
+        video_url: link(entry)?,
+        channel_name: text(author, ATOM, "name")?,
+        channel_url: text(author, ATOM, "uri")?,
+        published: datetime(&text(entry, ATOM, "published")?)?,
+        updated: datetime(&text(entry, ATOM, "updated")?)?,
+        route: RouteMatch::default(),
+        raw: xml.to_string(),
+    })
+}
+
+fn parse_deleted_entry(entry: Node, xml: &str) -> Result<DeletedEntry, NotificationError> {
+    // ref="yt:video:VIDEO_ID"
+    let reference = attribute(entry, "ref")?;
+    let video_id = reference
+        .strip_prefix("yt:video:")
+        .unwrap_or(reference)
+        .to_string();
+    let by = child(entry, TOMBSTONES, "by")?;
+    let channel_url = text(by, ATOM, "uri")?;
+    let channel_id = channel_url
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    Ok(DeletedEntry {
+        video_id,
+        channel_id,
+        video_url: link(entry)?,
+        channel_name: text(by, ATOM, "name")?,
+        channel_url,
+        deleted: datetime(attribute(entry, "when")?)?,
+        route: RouteMatch::default(),
+        raw: xml.to_string(),
+    })
+}
+
+fn child<'a, 'input>(
+    node: Node<'a, 'input>,
+    namespace: &str,
+    name: &str,
+) -> Result<Node<'a, 'input>, NotificationError> {
+    node.children()
+        .find(|n| n.has_tag_name((namespace, name)))
+        .ok_or_else(|| NotificationError::MissingParameter(name.to_string()))
+}
+
+/// Text of a child element, with the entities decoded.
+fn text(node: Node, namespace: &str, name: &str) -> Result<String, NotificationError> {
+    let child = child(node, namespace, name)?;
+    Ok(child
+        .children()
+        .filter_map(|n| n.text())
+        .collect::<String>()
+        .trim()
+        .to_string())
+}
+
+fn attribute<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str, NotificationError> {
+    node.attribute(name)
+        .ok_or_else(|| NotificationError::MissingParameter(name.to_string()))
+}
+
+/// `href` of the `<link>`, the alternate one if there are several.
+fn link(node: Node) -> Result<String, NotificationError> {
+    let mut links = node.children().filter(|n| n.has_tag_name((ATOM, "link")));
+    links
+        .clone()
+        .find(|n| n.attribute("rel") == Some("alternate"))
+        .or_else(|| links.next())
+        .and_then(|n| n.attribute("href"))
+        .map(ToString::to_string)
+        .ok_or_else(|| NotificationError::MissingParameter("link".to_string()))
+}
+
+fn datetime(value: &str) -> Result<OffsetDateTime, NotificationError> {
+    Ok(OffsetDateTime::parse(value, &Iso8601::DEFAULT)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLISHED: &str = r#"<?xml version='1.0' encoding='UTF-8'?>
+<feed xmlns:yt="http://www.youtube.com/xml/schemas/2015" xmlns="http://www.w3.org/2005/Atom"><link rel="hub" href="https://pubsubhubbub.appspot.com"/><link rel="self" href="https://www.youtube.com/xml/feeds/videos.xml?channel_id=UCXuqSBlHAE6Xw-yeJA0Tunw"/><title>YouTube video feed</title><updated>2024-02-29T18:04:17.151938722+00:00</updated><entry>
+  <id>yt:video:dQw4w9WgXcQ</id>
+  <yt:videoId>dQw4w9WgXcQ</yt:videoId>
+  <yt:channelId>UCXuqSBlHAE6Xw-yeJA0Tunw</yt:channelId>
+  <title>Tips &amp; Tricks: "Building" a PC</title>
+  <link rel="alternate" href="https://www.youtube.com/watch?v=dQw4w9WgXcQ"/>
+  <author>
+   <name>Linus Tech Tips</name>
+   <uri>https://www.youtube.com/channel/UCXuqSBlHAE6Xw-yeJA0Tunw</uri>
+  </author>
+  <published>2024-02-29T18:00:09+00:00</published>
+  <updated>2024-02-29T18:04:17.151938722+00:00</updated>
+ </entry></feed>
+"#;
+
+    const DELETED: &str = r#"<?xml version='1.0' encoding='UTF-8'?>
+<feed xmlns:at="http://purl.org/atompub/tombstones/1.0" xmlns="http://www.w3.org/2005/Atom"><at:deleted-entry ref="yt:video:dQw4w9WgXcQ" when="2024-03-01T09:12:45.312455+00:00">
+  <link href="https://www.youtube.com/watch?v=dQw4w9WgXcQ"/>
+  <at:by>
+   <name>Linus Tech Tips</name>
+   <uri>https://www.youtube.com/channel/UCXuqSBlHAE6Xw-yeJA0Tunw</uri>
+  </at:by>
+ </at:deleted-entry></feed>
+"#;
+
+    #[test]
+    fn published_entry() {
+        let events = parse_feed(PUBLISHED).unwrap();
+        assert_eq!(events.len(), 1);
+        let Event::Published(notification) = &events[0] else {
+            panic!("expected a published entry: {events:?}");
+        };
+
+        assert_eq!(notification.video_id, "dQw4w9WgXcQ");
+        assert_eq!(notification.channel_id, "UCXuqSBlHAE6Xw-yeJA0Tunw");
+        // Not the title of the feed, with the entities decoded
+        assert_eq!(
+            notification.video_title,
+            r#"Tips & Tricks: "Building" a PC"#
+        );
+        assert_eq!(
+            notification.video_url,
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+        assert_eq!(notification.channel_name, "Linus Tech Tips");
+        assert_eq!(
+            notification.channel_url,
+            "https://www.youtube.com/channel/UCXuqSBlHAE6Xw-yeJA0Tunw"
+        );
+        assert!(notification.is_new());
+    }
+
+    #[test]
+    fn several_entries() {
+        let xml = PUBLISHED.replace(
+            "</entry></feed>",
+            "</entry><entry><yt:videoId>abc</yt:videoId><yt:channelId>UC1</yt:channelId>\
+             <title>Second</title><link rel=\"alternate\" href=\"https://www.youtube.com/watch?v=abc\"/>\
+             <author><name>Other</name><uri>https://www.youtube.com/channel/UC1</uri></author>\
+             <published>2024-02-20T10:00:00+00:00</published>\
+             <updated>2024-02-29T10:00:00+00:00</updated></entry></feed>",
+        );
+        let events = parse_feed(&xml).unwrap();
+
+        assert_eq!(events.len(), 2);
+        let Event::Published(second) = &events[1] else {
+            panic!("expected a published entry: {events:?}");
+        };
+        assert_eq!(second.video_id, "abc");
+        assert!(!second.is_new());
+    }
+
+    #[test]
+    fn channel_feed() {
+        // https://www.youtube.com/feeds/videos.xml?channel_id=...
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns:yt="http://www.youtube.com/xml/schemas/2015" xmlns:media="http://search.yahoo.com/mrss/" xmlns="http://www.w3.org/2005/Atom">
+ <link rel="self" href="http://www.youtube.com/feeds/videos.xml?channel_id=UCXuqSBlHAE6Xw-yeJA0Tunw"/>
+ <id>yt:channel:XuqSBlHAE6Xw-yeJA0Tunw</id>
+ <yt:channelId>XuqSBlHAE6Xw-yeJA0Tunw</yt:channelId>
+ <title>Linus Tech Tips</title>
+ <link rel="alternate" href="https://www.youtube.com/channel/UCXuqSBlHAE6Xw-yeJA0Tunw"/>
+ <author>
+  <name>Linus Tech Tips</name>
+  <uri>https://www.youtube.com/channel/UCXuqSBlHAE6Xw-yeJA0Tunw</uri>
+ </author>
+ <published>2008-11-25T00:46:52+00:00</published>
+ <entry>
+  <id>yt:video:dQw4w9WgXcQ</id>
+  <yt:videoId>dQw4w9WgXcQ</yt:videoId>
+  <yt:channelId>UCXuqSBlHAE6Xw-yeJA0Tunw</yt:channelId>
+  <title>Tips &amp; Tricks</title>
+  <link rel="alternate" href="https://www.youtube.com/watch?v=dQw4w9WgXcQ"/>
+  <author>
+   <name>Linus Tech Tips</name>
+   <uri>https://www.youtube.com/channel/UCXuqSBlHAE6Xw-yeJA0Tunw</uri>
+  </author>
+  <published>2024-02-29T18:00:09+00:00</published>
+  <updated>2024-02-29T18:04:17+00:00</updated>
+  <media:group>
+   <media:title>Tips &amp; Tricks</media:title>
+   <media:thumbnail url="https://i1.ytimg.com/vi/dQw4w9WgXcQ/hqdefault.jpg" width="480" height="360"/>
+  </media:group>
+ </entry>
+</feed>
+"#;
+        let events = parse_feed(xml).unwrap();
+        assert_eq!(events.len(), 1);
+        let Event::Published(notification) = &events[0] else {
+            panic!("expected a published entry: {events:?}");
+        };
+
+        assert_eq!(notification.video_id, "dQw4w9WgXcQ");
+        assert_eq!(notification.channel_id, "UCXuqSBlHAE6Xw-yeJA0Tunw");
+        assert_eq!(notification.video_title, "Tips & Tricks");
+    }
+
+    #[test]
+    fn deleted_entry() {
+        let events = parse_feed(DELETED).unwrap();
+        assert_eq!(events.len(), 1);
+        let Event::Deleted(deleted) = &events[0] else {
+            panic!("expected a deleted entry: {events:?}");
+        };
+
+        assert_eq!(deleted.video_id, "dQw4w9WgXcQ");
+        assert_eq!(deleted.channel_id, "UCXuqSBlHAE6Xw-yeJA0Tunw");
+        assert_eq!(deleted.channel_name, "Linus Tech Tips");
+        assert_eq!(
+            deleted.video_url,
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+    }
+
+    #[test]
+    fn missing_field() {
+        let xml = PUBLISHED.replace("<yt:videoId>dQw4w9WgXcQ</yt:videoId>", "");
+        assert!(matches!(
+            parse_feed(&xml),
+            Err(NotificationError::MissingParameter(p)) if p == "videoId"
+        ));
+    }
+
+    #[test]
+    fn invalid_xml() {
+        assert!(matches!(
+            parse_feed("<feed><entry></feed>"),
+            Err(NotificationError::Xml(_))
+        ));
+    }
 }
@@ -1,4 +1,7 @@
-pub use crate::error::Error;
-pub use crate::notification::Notification;
+pub use crate::error::{
+    BuilderError, Error, HandleConnectionError, NotificationError, ParseError, SignatureError,
+};
+pub use crate::notification::{DeletedEntry, Event, Notification};
+pub use crate::router::RouteMatch;
 pub use crate::HookListener;
 pub use crate::Mode;
@@ -1,20 +1,22 @@
 use std::{
     collections::HashMap,
     fmt::{self},
-    path::Path,
 };
 
-use crate::error::ParseError;
+use crate::{
+    error::ParseError,
+    http::{self, Headers, Unframed},
+};
 
 #[derive(Debug)]
-pub(super) struct RequestLine<'a> {
-    pub(super) method: &'a str,
-    pub(super) path: &'a Path,
-    pub(super) params: Option<HashMap<&'a str, &'a str>>,
-    pub(super) http_version: &'a str,
+pub(super) struct RequestLine {
+    pub(super) method: String,
+    pub(super) path: String,
+    pub(super) params: Option<HashMap<String, String>>,
+    pub(super) http_version: String,
 }
 
-impl<'a> fmt::Display for RequestLine<'a> {
+impl fmt::Display for RequestLine {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let params = if let Some(map) = &self.params {
             let mut params = String::from("?");
@@ -29,51 +31,44 @@ impl<'a> fmt::Display for RequestLine<'a> {
         write!(
             f,
             "{} {}{} {}\r\n",
-            self.method,
-            self.path.display(),
-            params,
-            self.http_version
+            self.method, self.path, params, self.http_version
         )
     }
 }
 
-#[allow(unused)]
 #[derive(Debug)]
-pub(super) struct Request<'a> {
-    pub(super) request_line: RequestLine<'a>,
-    pub(super) headers: HashMap<&'a str, &'a str>,
-    pub(super) body: Option<&'a str>,
+pub(super) struct Request {
+    pub(super) request_line: RequestLine,
+    pub(super) headers: Headers,
+    pub(super) body: Vec<u8>,
 }
 
-impl<'a> Request<'a> {
-    pub(super) fn try_parse(request: &'a str) -> Result<Self, ParseError> {
-        let mut request_line = None;
-        let mut headers = HashMap::new();
-
-        for (i, line) in request.lines().enumerate() {
-            if !line.is_empty() {
-                if i == 0 {
-                    request_line = Some(parse_request_line(line)?);
-                } else {
-                    let (key, value) = line
-                        .split_once(": ")
-                        .ok_or_else(|| ParseError::ParameterError("error in header".to_string()))?;
-                    headers.insert(key, value);
-                }
-            } else {
-                break;
-            }
-        }
+impl Request {
+    /// Parse a request from the bytes received so far.
+    ///
+    /// Returns `None` if the request is not complete yet; the body length is given by
+    /// `Content-Length` or `Transfer-Encoding: chunked`.
+    pub(super) fn try_parse(buf: &[u8]) -> Result<Option<Self>, ParseError> {
+        let Some(head) = http::parse_head(buf)? else {
+            return Ok(None);
+        };
+        let request_line = parse_request_line(head.start_line)?;
 
-        let empty_line = request.find("\r\n\r\n");
-        let body = empty_line.map(|i| &request[(i + 1)..]);
+        let Some(body) = http::parse_body(&head.headers, &buf[head.body_start..], Unframed::Empty)?
+        else {
+            return Ok(None);
+        };
 
-        Ok(Self {
-            request_line: request_line
-                .ok_or_else(|| ParseError::NotFound("request line".to_string()))?,
-            headers,
+        Ok(Some(Self {
+            request_line,
+            headers: head.headers,
             body,
-        })
+        }))
+    }
+
+    /// Get the value of a header, ignoring the case of its name.
+    pub(super) fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
     }
 }
 
@@ -88,43 +83,102 @@ pub(super) fn parse_request_line(request_line: &str) -> Result<RequestLine, Pars
         .next()
         .ok_or_else(|| ParseError::NotFound("URI".to_string()))?;
 
-    let (path, params) = {
-        if uri.contains('?') {
-            let (path, params_string) = uri.split_once('?').ok_or_else(|| {
-                ParseError::ParameterError("No parameters in request".to_string())
-            })?;
-
-            let params_vec = params_string.split('&');
-            let mut params = HashMap::new();
-            for p in params_vec {
-                let (key, value) = p.split_once('=').ok_or_else(|| {
-                    ParseError::ParameterError("Parameter found is not key=value".to_string())
-                })?;
-                params.insert(key, value);
-            }
-
-            (Path::new(path), Some(params))
-        } else {
-            (Path::new(uri), None)
-        }
+    let (path, params) = match uri.split_once('?') {
+        Some((path, query)) => (path, Some(http::parse_query(query))),
+        None => (uri, None),
     };
 
-    let norm_uri = path.to_str().expect("Invalid unicode!");
-
-    const ROOT: &str = ".";
-
-    if !Path::new(&format!("{ROOT}{norm_uri}")).exists() {
-        Err(ParseError::UriError)?;
-    }
-
     let http_version = parts
         .next()
+        .filter(|version| version.starts_with("HTTP/"))
         .ok_or_else(|| ParseError::NotFound("HTTP version".to_string()))?;
 
     Ok(RequestLine {
-        method,
-        path,
+        method: method.to_string(),
+        path: path.to_string(),
         params,
-        http_version,
+        http_version: http_version.to_string(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Verification of intent, as sent by pubsubhubbub.appspot.com
+    const VERIFICATION: &str = "GET /?hub.topic=https%3A%2F%2Fwww.youtube.com%2Fxml%2Ffeeds%2Fvideos.xml%3Fchannel_id%3DUCXuqSBlHAE6Xw-yeJA0Tunw&hub.challenge=7836469911285925402&hub.mode=subscribe&hub.lease_seconds=432000 HTTP/1.1\r
+Host: brztek.example.com\r
+Accept: */*\r
+From: googlebot(at)googlebot.com\r
+User-Agent: FeedFetcher-Google; (+http://www.google.com/feedfetcher.html)\r
+Accept-Encoding: gzip,deflate,br\r
+\r
+";
+
+    // Content distribution, with the Atom payload cut to keep the test readable
+    const DISTRIBUTION: &str = "POST / HTTP/1.1\r
+Content-Type: application/atom+xml\r
+Cache-Control: no-cache,max-age=0\r
+Pragma: no-cache\r
+Link: <https://pubsubhubbub.appspot.com>; rel=hub, <https://www.youtube.com/xml/feeds/videos.xml?channel_id=UCXuqSBlHAE6Xw-yeJA0Tunw>; rel=self\r
+X-Hub-Signature: sha1=d4b2f53a9b1cbc3a0e8f8e1f0b1a43d21f9d5f4c\r
+Content-Length: 39\r
+Host: brztek.example.com\r
+Accept: */*\r
+From: googlebot(at)googlebot.com\r
+User-Agent: FeedFetcher-Google; (+http://www.google.com/feedfetcher.html)\r
+Accept-Encoding: gzip, deflate, br\r
+\r
+<?xml version='1.0' encoding='UTF-8'?>\n";
+
+    #[test]
+    fn parse_verification() {
+        let request = Request::try_parse(VERIFICATION.as_bytes())
+            .unwrap()
+            .unwrap();
+        let params = request.request_line.params.as_ref().unwrap();
+
+        assert_eq!(request.request_line.method, "GET");
+        assert_eq!(request.request_line.path, "/");
+        assert_eq!(
+            params["hub.topic"],
+            "https://www.youtube.com/xml/feeds/videos.xml?channel_id=UCXuqSBlHAE6Xw-yeJA0Tunw"
+        );
+        assert_eq!(params["hub.challenge"], "7836469911285925402");
+        assert_eq!(params["hub.lease_seconds"], "432000");
+        assert_eq!(request.header("from"), Some("googlebot(at)googlebot.com"));
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn parse_distribution() {
+        let request = Request::try_parse(DISTRIBUTION.as_bytes())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(request.request_line.method, "POST");
+        assert_eq!(
+            request.header("x-hub-signature"),
+            Some("sha1=d4b2f53a9b1cbc3a0e8f8e1f0b1a43d21f9d5f4c")
+        );
+        assert_eq!(request.body, b"<?xml version='1.0' encoding='UTF-8'?>\n");
+    }
+
+    #[test]
+    fn distribution_split_across_segments() {
+        let bytes = DISTRIBUTION.as_bytes();
+
+        for split in [10, bytes.len() - 39, bytes.len() - 1] {
+            assert!(Request::try_parse(&bytes[..split]).unwrap().is_none());
+        }
+        assert!(Request::try_parse(bytes).unwrap().is_some());
+    }
+
+    #[test]
+    fn missing_http_version() {
+        assert!(matches!(
+            Request::try_parse(b"GET /\r\n\r\n"),
+            Err(ParseError::NotFound(_))
+        ));
+    }
+}
@@ -1,23 +1,26 @@
 #![allow(unused)]
-use std::{collections::HashMap, fmt};
+use std::fmt;
 
-use crate::error::ParseError;
+use crate::{
+    error::ParseError,
+    http::{self, Headers, Unframed},
+};
 
 #[derive(Debug)]
-pub(super) struct ResponseLine<'a> {
-    pub(super) http_version: &'a str,
-    pub(super) status_code: &'a str,
-    pub(super) status_message: &'a str,
+pub(super) struct ResponseLine {
+    pub(super) http_version: String,
+    pub(super) status_code: String,
+    pub(super) status_message: String,
 }
 
 #[derive(Debug)]
-pub(super) struct Response<'a> {
-    pub(super) status_line: ResponseLine<'a>,
-    pub(super) headers: HashMap<&'a str, &'a str>,
-    pub(super) body: Option<&'a str>,
+pub(super) struct Response {
+    pub(super) status_line: ResponseLine,
+    pub(super) headers: Headers,
+    pub(super) body: Vec<u8>,
 }
 
-impl<'a> fmt::Display for Response<'a> {
+impl fmt::Display for Response {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -29,45 +32,91 @@ impl<'a> fmt::Display for Response<'a> {
     }
 }
 
-impl<'a> Response<'a> {
-    pub(super) fn parse(response: &'a str) -> Result<Self, ParseError> {
-        let mut http_version = None;
-        let mut status_code = None;
-        let mut status_message = None;
-        let mut headers = HashMap::new();
-        for (i, line) in response.lines().enumerate() {
-            if !line.is_empty() {
-                if i == 0 {
-                    let mut status_line = line.split_whitespace();
-                    http_version = status_line.next();
-                    status_code = status_line.next();
-                    status_message = status_line.next();
-                } else {
-                    let (key, value) = line
-                        .split_once(": ")
-                        .ok_or_else(|| ParseError::HeaderError(line.to_string()))?;
-                    headers.insert(key, value);
-                }
-            } else {
-                break;
-            }
-        }
-        let status_line = ResponseLine {
-            http_version: http_version
-                .ok_or_else(|| ParseError::NotFound("HTTP version".to_string()))?,
-            status_code: status_code
-                .ok_or_else(|| ParseError::NotFound("Status code".to_string()))?,
-            status_message: status_message
-                .ok_or_else(|| ParseError::NotFound("Status message".to_string()))?,
+impl Response {
+    /// Parse a response from the bytes received so far.
+    ///
+    /// Returns `None` if the response is not complete yet. Without `Content-Length` or
+    /// `Transfer-Encoding`, the body runs until the connection is closed (`eof`).
+    pub(super) fn parse(buf: &[u8], eof: bool) -> Result<Option<Self>, ParseError> {
+        let Some(head) = http::parse_head(buf)? else {
+            return Ok(None);
         };
 
-        let empty_line = response.find("\r\n\r\n");
-        let body = empty_line.map(|i| &response[(i + 1)..]);
+        let mut status_line = head.start_line.splitn(3, ' ');
+        let http_version = status_line
+            .next()
+            .ok_or_else(|| ParseError::NotFound("HTTP version".to_string()))?;
+        let status_code = status_line
+            .next()
+            .ok_or_else(|| ParseError::NotFound("Status code".to_string()))?;
+        // The reason phrase may be empty
+        let status_message = status_line.next().unwrap_or_default();
 
-        Ok(Self {
-            status_line,
-            headers,
+        let Some(body) = http::parse_body(
+            &head.headers,
+            &buf[head.body_start..],
+            Unframed::UntilEof { eof },
+        )?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            status_line: ResponseLine {
+                http_version: http_version.to_string(),
+                status_code: status_code.to_string(),
+                status_message: status_message.to_string(),
+            },
+            headers: head.headers,
             body,
-        })
+        }))
+    }
+}
+
+/// Format a `200 OK` response with its `Content-Length`.
+pub(super) fn ok(body: &str) -> String {
+    format(200, "OK", body)
+}
+
+/// Format a `404 Not Found` response, for requests outside of the callback routes.
+pub(super) fn not_found() -> String {
+    format(404, "Not Found", "")
+}
+
+fn format(status_code: u16, status_message: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status_code} {status_message}\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hub_error() {
+        let buf = b"HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 17\r\n\r\nInvalid hub.mode\n";
+        let response = Response::parse(buf, false).unwrap().unwrap();
+
+        assert_eq!(response.status_line.status_code, "400");
+        assert_eq!(response.status_line.status_message, "Bad Request");
+        assert_eq!(response.body, b"Invalid hub.mode\n");
+    }
+
+    #[test]
+    fn body_until_eof() {
+        let buf = b"HTTP/1.1 202 Accepted\r\n\r\nok";
+
+        assert!(Response::parse(buf, false).unwrap().is_none());
+        assert_eq!(Response::parse(buf, true).unwrap().unwrap().body, b"ok");
+    }
+
+    #[test]
+    fn ok_has_content_length() {
+        assert_eq!(
+            ok("1234"),
+            "HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n1234"
+        );
     }
 }
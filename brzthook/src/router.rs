@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use crate::error::BuilderError;
+
+/// Name of the route parameter holding the subscription id.
+const ID_PARAM: &str = "id";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+/// Callback route, e.g. `/youtube/{id}`.
+///
+/// Segments between braces are parameters; `{id}` is replaced by the subscription id
+/// in the callback URL sent to the hub.
+#[derive(Debug, Clone)]
+pub(super) struct Route {
+    pattern: String,
+    segments: Vec<Segment>,
+}
+
+impl Route {
+    pub(super) fn parse(pattern: &str) -> Result<Self, BuilderError> {
+        let invalid = || BuilderError::InvalidRoute(pattern.to_string());
+
+        let path = pattern.strip_prefix('/').ok_or_else(invalid)?;
+        let mut segments = vec![];
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            let segment = match segment.strip_prefix('{') {
+                Some(param) => {
+                    let name = param.strip_suffix('}').ok_or_else(invalid)?;
+                    if name.is_empty() || name.contains(['{', '}']) {
+                        return Err(invalid());
+                    }
+                    Segment::Param(name.to_string())
+                }
+                None if segment.contains(['{', '}']) => return Err(invalid()),
+                None => Segment::Literal(segment.to_string()),
+            };
+            segments.push(segment);
+        }
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            segments,
+        })
+    }
+
+    fn matches(&self, path: &str) -> Option<RouteMatch> {
+        let mut parts = path.split('/').filter(|s| !s.is_empty());
+        let mut params = HashMap::new();
+
+        for segment in &self.segments {
+            let part = parts.next()?;
+            match segment {
+                Segment::Literal(literal) if literal == part => {}
+                Segment::Literal(_) => return None,
+                Segment::Param(name) => {
+                    params.insert(name.clone(), part.to_string());
+                }
+            }
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(RouteMatch {
+            pattern: self.pattern.clone(),
+            params,
+        })
+    }
+
+    /// Path of the route for the subscription `id`.
+    fn path(&self, id: &str) -> String {
+        let path = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => literal.as_str(),
+                Segment::Param(name) if name == ID_PARAM => id,
+                Segment::Param(name) => name.as_str(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        format!("/{path}")
+    }
+}
+
+/// Route matched by an incoming request, with the values of its parameters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteMatch {
+    pub pattern: String,
+    pub params: HashMap<String, String>,
+}
+
+impl RouteMatch {
+    /// Subscription id given by the `{id}` parameter of the route.
+    pub fn id(&self) -> Option<&str> {
+        self.params.get(ID_PARAM).map(String::as_str)
+    }
+}
+
+/// Routes the listener accepts requests on.
+///
+/// The first route is the one used to build callback URLs; without any route, the
+/// listener only accepts requests on `/`.
+#[derive(Debug, Clone)]
+pub(super) struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub(super) fn new(routes: Vec<Route>) -> Self {
+        let routes = if routes.is_empty() {
+            vec![Route {
+                pattern: "/".to_string(),
+                segments: vec![],
+            }]
+        } else {
+            routes
+        };
+        Self { routes }
+    }
+
+    pub(super) fn matches(&self, path: &str) -> Option<RouteMatch> {
+        self.routes.iter().find_map(|route| route.matches(path))
+    }
+
+    /// Callback URL of the subscription `id`, under the public `base` URL.
+    pub(super) fn callback_url(&self, base: &str, id: &str) -> String {
+        match self.routes.first().map(|route| route.path(id)) {
+            Some(path) if path != "/" => format!("{}{path}", base.trim_end_matches('/')),
+            _ => base.to_string(),
+        }
+    }
+}
+
+/// Find the subscription id in a topic URL built from `template`.
+pub(super) fn topic_id(template: &str, topic: &str) -> Option<String> {
+    let (prefix, suffix) = template.split_once("{id}")?;
+    let id = topic.strip_prefix(prefix)?.strip_suffix(suffix)?;
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router(patterns: &[&str]) -> Router {
+        Router::new(
+            patterns
+                .iter()
+                .map(|pattern| Route::parse(pattern).unwrap())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn match_subscription_id() {
+        let router = router(&["/youtube/{id}"]);
+        let route = router.matches("/youtube/UCXuqSBlHAE6Xw-yeJA0Tunw").unwrap();
+
+        assert_eq!(route.pattern, "/youtube/{id}");
+        assert_eq!(route.id(), Some("UCXuqSBlHAE6Xw-yeJA0Tunw"));
+        assert!(router.matches("/youtube").is_none());
+        assert!(router.matches("/youtube/UC1/extra").is_none());
+        assert!(router.matches("/").is_none());
+    }
+
+    #[test]
+    fn several_routes() {
+        let router = router(&["/youtube/{id}", "/twitch/{user}/{id}"]);
+        let route = router.matches("/twitch/someone/123").unwrap();
+
+        assert_eq!(route.pattern, "/twitch/{user}/{id}");
+        assert_eq!(route.params["user"], "someone");
+        assert_eq!(route.id(), Some("123"));
+    }
+
+    #[test]
+    fn default_route() {
+        let router = router(&[]);
+
+        assert_eq!(router.matches("/").unwrap().id(), None);
+        assert!(router.matches("/index.html").is_none());
+        assert_eq!(
+            router.callback_url("https://example.com/", "UC1"),
+            "https://example.com/"
+        );
+    }
+
+    #[test]
+    fn callback_url() {
+        let router = router(&["/youtube/{id}"]);
+
+        assert_eq!(
+            router.callback_url("https://example.com/hooks/", "UC1"),
+            "https://example.com/hooks/youtube/UC1"
+        );
+    }
+
+    #[test]
+    fn id_from_topic() {
+        let template = "https://www.youtube.com/xml/feeds/videos.xml?channel_id={id}";
+
+        assert_eq!(
+            topic_id(
+                template,
+                "https://www.youtube.com/xml/feeds/videos.xml?channel_id=UC1"
+            ),
+            Some("UC1".to_string())
+        );
+        assert_eq!(topic_id(template, "https://example.com/feed"), None);
+        assert_eq!(
+            topic_id("https://example.com/feed", "https://example.com/feed"),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_routes() {
+        for pattern in ["youtube/{id}", "/youtube/{id", "/youtube/{}", "/you{tube}"] {
+            assert!(Route::parse(pattern).is_err(), "{pattern}");
+        }
+    }
+}
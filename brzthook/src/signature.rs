@@ -0,0 +1,44 @@
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng};
+use sha1::Sha1;
+use sha2::Sha256;
+
+use crate::error::SignatureError;
+
+const SECRET_LEN: usize = 40;
+
+/// Generate a random alphanumeric secret to send as `hub.secret`.
+pub(super) fn generate_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// Verify the `X-Hub-Signature` header value of a content distribution request.
+///
+/// The header has the form `method=signature` where signature is the hex encoded
+/// HMAC of the body, computed with the secret given to the hub on subscription.
+pub(super) fn verify(secret: &str, body: &[u8], header: &str) -> Result<(), SignatureError> {
+    let (method, signature) = header
+        .split_once('=')
+        .ok_or_else(|| SignatureError::Malformed(header.to_string()))?;
+    let signature =
+        hex::decode(signature).map_err(|_| SignatureError::Malformed(header.to_string()))?;
+
+    let verified = match method {
+        "sha1" => Hmac::<Sha1>::new_from_slice(secret.as_bytes())
+            .map(|mac| mac.chain_update(body).verify_slice(&signature).is_ok()),
+        "sha256" => Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .map(|mac| mac.chain_update(body).verify_slice(&signature).is_ok()),
+        _ => return Err(SignatureError::UnsupportedMethod(method.to_string())),
+    }
+    .map_err(|_| SignatureError::InvalidKey)?;
+
+    if verified {
+        Ok(())
+    } else {
+        Err(SignatureError::Mismatch)
+    }
+}
@@ -0,0 +1,260 @@
+//! Harness playing the hub and the publisher against a `HookListener`.
+#![allow(dead_code)]
+
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use brzthook::prelude::*;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::Receiver,
+    task::JoinHandle,
+};
+
+pub const CHANNEL_ID: &str = "UCXuqSBlHAE6Xw-yeJA0Tunw";
+pub const TOPIC: &str = "https://www.youtube.com/xml/feeds/videos.xml?channel_id={id}";
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Listener bound to an ephemeral port, with the receiver of its events.
+pub struct Harness {
+    pub listener: HookListener,
+    pub rx: Receiver<Result<Event, Error>>,
+    pub addr: SocketAddr,
+}
+
+impl Harness {
+    pub fn start(new_only: bool) -> Self {
+        Self::with_hub(new_only, "https://pubsubhubbub.appspot.com")
+    }
+
+    pub fn with_hub(new_only: bool, hub: &str) -> Self {
+        let listener = HookListener::builder()
+            .listener("127.0.0.1", 0)
+            .unwrap()
+            .callback("http://brztek.example.com/hooks")
+            .routes(["/youtube/{id}"])
+            .unwrap()
+            .hub(hub)
+            .topic(TOPIC)
+            .new_only(new_only)
+            .build()
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let rx = listener.listen().unwrap();
+
+        Self { listener, rx, addr }
+    }
+
+    /// Listener whose `mode` request for the subscription `id` was accepted by a hub, and
+    /// waits for its verification.
+    pub async fn requested(id: &str, mode: Mode) -> Self {
+        let hub = FakeHub::bind().await;
+        let harness = Self::with_hub(false, &hub.url());
+        let hub = hub.accept();
+        harness.listener.subscribe(id, mode).await.unwrap();
+        hub.await.unwrap();
+
+        harness
+    }
+
+    /// Next event sent by the listener.
+    pub async fn recv(&mut self) -> Result<Event, Error> {
+        tokio::time::timeout(RECV_TIMEOUT, self.rx.recv())
+            .await
+            .expect("no event received")
+            .expect("listener stopped")
+    }
+
+    /// Assert that the listener does not send anything for a while.
+    pub async fn assert_silent(&mut self) {
+        let received = tokio::time::timeout(Duration::from_millis(200), self.rx.recv()).await;
+        assert!(received.is_err(), "unexpected event: {received:?}");
+    }
+
+    /// Send raw bytes to the listener and return its response.
+    pub async fn send(&self, bytes: &[u8]) -> String {
+        send(self.addr, bytes).await
+    }
+
+    /// Send a verification of intent, as the hub does after a subscription request.
+    pub async fn verify(&self, id: &str, mode: &str, challenge: &str) -> String {
+        self.send(verification(id, mode, challenge).as_bytes())
+            .await
+    }
+
+    /// Send the denial of a subscription.
+    pub async fn deny(&self, id: &str, reason: &str) -> String {
+        let topic = encode(&TOPIC.replace("{id}", id));
+        let reason = encode(reason);
+        let request = format!(
+            "GET /youtube/{id}?hub.mode=denied&hub.topic={topic}&hub.reason={reason} HTTP/1.1\r\n\
+             Host: brztek.example.com\r\n\r\n"
+        );
+        self.send(request.as_bytes()).await
+    }
+
+    /// Distribute a feed to the subscription `id`, signed with `secret`.
+    pub async fn publish(&self, id: &str, secret: &str, feed: &str) -> String {
+        let request = format!(
+            "POST /youtube/{id} HTTP/1.1\r\n\
+             Content-Type: application/atom+xml\r\n\
+             X-Hub-Signature: {}\r\n\
+             Content-Length: {}\r\n\r\n{feed}",
+            sign(secret, feed.as_bytes()),
+            feed.len()
+        );
+        self.send(request.as_bytes()).await
+    }
+}
+
+/// Verification of intent sent by the hub.
+pub fn verification(id: &str, mode: &str, challenge: &str) -> String {
+    let topic = encode(&TOPIC.replace("{id}", id));
+    format!(
+        "GET /youtube/{id}?hub.topic={topic}&hub.mode={mode}&hub.lease_seconds=432000&hub.challenge={challenge} HTTP/1.1\r\n\
+         Host: brztek.example.com\r\n\
+         From: googlebot(at)googlebot.com\r\n\r\n"
+    )
+}
+
+pub async fn send(addr: SocketAddr, bytes: impl AsRef<[u8]>) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(bytes.as_ref()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes())
+        .unwrap()
+        .chain_update(body)
+        .finalize();
+    format!("sha1={}", hex::encode(mac.into_bytes()))
+}
+
+fn encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// Atom feed with one entry, as pushed by YouTube.
+pub fn feed(video_id: &str, title: &str, published: &str, updated: &str) -> String {
+    format!(
+        r#"<?xml version='1.0' encoding='UTF-8'?>
+<feed xmlns:yt="http://www.youtube.com/xml/schemas/2015" xmlns="http://www.w3.org/2005/Atom"><link rel="hub" href="https://pubsubhubbub.appspot.com"/><link rel="self" href="https://www.youtube.com/xml/feeds/videos.xml?channel_id={CHANNEL_ID}"/><title>YouTube video feed</title><updated>{updated}</updated><entry>
+  <id>yt:video:{video_id}</id>
+  <yt:videoId>{video_id}</yt:videoId>
+  <yt:channelId>{CHANNEL_ID}</yt:channelId>
+  <title>{title}</title>
+  <link rel="alternate" href="https://www.youtube.com/watch?v={video_id}"/>
+  <author>
+   <name>Linus Tech Tips</name>
+   <uri>https://www.youtube.com/channel/{CHANNEL_ID}</uri>
+  </author>
+  <published>{published}</published>
+  <updated>{updated}</updated>
+ </entry></feed>
+"#
+    )
+}
+
+/// Hub accepting one subscription request, then verifying it against the callback.
+pub struct FakeHub {
+    listener: TcpListener,
+    pub addr: SocketAddr,
+}
+
+impl FakeHub {
+    pub async fn bind() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        Self { listener, addr }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Accept the next subscription request without verifying it, e.g. to verify or deny
+    /// it by hand.
+    ///
+    /// Returns the parameters of the subscription request.
+    pub fn accept(self) -> JoinHandle<HashMap<String, String>> {
+        tokio::spawn(async move {
+            let (mut stream, _) = self.listener.accept().await.unwrap();
+            let body = read_body(&mut stream).await;
+            let response = "HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\n\r\n";
+            stream.write_all(response.as_bytes()).await.unwrap();
+
+            url::form_urlencoded::parse(body.as_bytes())
+                .into_owned()
+                .collect()
+        })
+    }
+
+    /// Answer the next subscription request with `status`, and verify it by sending the
+    /// challenge to `callback_addr`, in place of the public callback URL.
+    ///
+    /// Returns the parameters of the subscription request and the response to the
+    /// verification.
+    pub fn serve(
+        self,
+        status: &'static str,
+        callback_addr: SocketAddr,
+    ) -> JoinHandle<(HashMap<String, String>, String)> {
+        tokio::spawn(async move {
+            let (mut stream, _) = self.listener.accept().await.unwrap();
+            let body = read_body(&mut stream).await;
+            let params: HashMap<String, String> = url::form_urlencoded::parse(body.as_bytes())
+                .into_owned()
+                .collect();
+
+            let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n");
+            stream.write_all(response.as_bytes()).await.unwrap();
+            drop(stream);
+            if !status.starts_with('2') {
+                return (params, String::new());
+            }
+
+            // Verification of intent on the path of the callback URL
+            let path = params["hub.callback"]
+                .strip_prefix("http://brztek.example.com/hooks")
+                .unwrap()
+                .to_string();
+            let topic = encode(&params["hub.topic"]);
+            let mode = &params["hub.mode"];
+            let request = format!(
+                "GET {path}?hub.mode={mode}&hub.topic={topic}&hub.challenge=1234567890&hub.lease_seconds=864000 HTTP/1.1\r\n\r\n"
+            );
+            let verification = send(callback_addr, request.as_bytes()).await;
+
+            (params, verification)
+        })
+    }
+}
+
+async fn read_body(stream: &mut TcpStream) -> String {
+    let mut received = vec![];
+    let mut buf = [0; 1024];
+    loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        received.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&received);
+        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+            let len = head
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or(0);
+            if body.len() >= len {
+                return body.to_string();
+            }
+        }
+        assert!(n > 0, "connection closed before the end of the request");
+    }
+}
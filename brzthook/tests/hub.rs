@@ -0,0 +1,238 @@
+mod common;
+
+use brzthook::prelude::*;
+use common::{feed, FakeHub, Harness, CHANNEL_ID};
+
+const NEW_PUBLISHED: &str = "2024-02-29T18:00:09+00:00";
+const NEW_UPDATED: &str = "2024-02-29T18:04:17.151938722+00:00";
+const OLD_PUBLISHED: &str = "2023-11-02T10:00:00+00:00";
+
+#[tokio::test]
+async fn subscription_is_verified() {
+    let hub = FakeHub::bind().await;
+    let mut harness = Harness::with_hub(false, &hub.url());
+    let hub = hub.serve("202 Accepted", harness.addr);
+
+    let status = harness
+        .listener
+        .subscribe(CHANNEL_ID, Mode::Subscribe)
+        .await
+        .unwrap();
+    let (params, verification) = hub.await.unwrap();
+
+    assert_eq!(status, 202);
+    assert_eq!(
+        params["hub.callback"],
+        format!("http://brztek.example.com/hooks/youtube/{CHANNEL_ID}")
+    );
+    assert_eq!(
+        params["hub.topic"],
+        format!("https://www.youtube.com/xml/feeds/videos.xml?channel_id={CHANNEL_ID}")
+    );
+    assert_eq!(params["hub.mode"], "subscribe");
+    assert_eq!(
+        Some(&params["hub.secret"]),
+        harness.listener.secret(CHANNEL_ID).as_ref()
+    );
+    assert!(verification.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(verification.ends_with("\r\n\r\n1234567890"));
+
+    match harness.recv().await.unwrap() {
+        Event::Verified {
+            id,
+            mode,
+            lease_seconds,
+            ..
+        } => {
+            assert_eq!(id.as_deref(), Some(CHANNEL_ID));
+            assert_eq!(mode, Mode::Subscribe);
+            assert_eq!(lease_seconds, Some(864_000));
+        }
+        event => panic!("unexpected event: {event:?}"),
+    }
+}
+
+#[tokio::test]
+async fn subscription_rejected_by_hub() {
+    let hub = FakeHub::bind().await;
+    let harness = Harness::with_hub(false, &hub.url());
+    let hub = hub.serve("400 Bad Request", harness.addr);
+
+    let result = harness
+        .listener
+        .subscribe(CHANNEL_ID, Mode::Subscribe)
+        .await;
+    hub.await.unwrap();
+
+    assert!(matches!(result, Err(Error::Hub { status: 400, .. })));
+}
+
+#[tokio::test]
+async fn unsubscription_is_verified() {
+    let mut harness = Harness::requested(CHANNEL_ID, Mode::Unsubscribe).await;
+
+    let response = harness.verify(CHANNEL_ID, "unsubscribe", "abc").await;
+
+    assert!(response.ends_with("\r\n\r\nabc"));
+    assert!(matches!(
+        harness.recv().await.unwrap(),
+        Event::Verified {
+            mode: Mode::Unsubscribe,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn subscription_denied() {
+    let mut harness = Harness::requested(CHANNEL_ID, Mode::Subscribe).await;
+
+    let response = harness.deny(CHANNEL_ID, "Topic not found").await;
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    match harness.recv().await.unwrap() {
+        Event::Denied { id, reason, .. } => {
+            assert_eq!(id.as_deref(), Some(CHANNEL_ID));
+            assert_eq!(reason, "Topic not found");
+        }
+        event => panic!("unexpected event: {event:?}"),
+    }
+}
+
+#[tokio::test]
+async fn unsolicited_verification_is_ignored() {
+    let mut harness = Harness::start(false);
+
+    let verified = harness.verify(CHANNEL_ID, "subscribe", "abc").await;
+    let denied = harness.deny(CHANNEL_ID, "Topic not found").await;
+
+    assert!(verified.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(!verified.ends_with("abc"));
+    assert!(denied.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    harness.assert_silent().await;
+}
+
+#[tokio::test]
+async fn verification_is_answered_once() {
+    let hub = FakeHub::bind().await;
+    let mut harness = Harness::with_hub(false, &hub.url());
+    let hub = hub.serve("202 Accepted", harness.addr);
+    harness
+        .listener
+        .subscribe(CHANNEL_ID, Mode::Subscribe)
+        .await
+        .unwrap();
+    hub.await.unwrap();
+    harness.recv().await.unwrap();
+
+    // A replayed verification cannot change the lease
+    let response = harness.verify(CHANNEL_ID, "subscribe", "abc").await;
+
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    harness.assert_silent().await;
+}
+
+#[tokio::test]
+async fn new_video_is_notified() {
+    let mut harness = Harness::start(true);
+    harness.listener.set_secret(CHANNEL_ID, "secret");
+
+    let feed = feed(
+        "dQw4w9WgXcQ",
+        "Tips &amp; Tricks",
+        NEW_PUBLISHED,
+        NEW_UPDATED,
+    );
+    let response = harness.publish(CHANNEL_ID, "secret", &feed).await;
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    match harness.recv().await.unwrap() {
+        Event::Published(notification) => {
+            assert_eq!(notification.video_id, "dQw4w9WgXcQ");
+            assert_eq!(notification.video_title, "Tips & Tricks");
+            assert_eq!(notification.channel_id, CHANNEL_ID);
+            assert_eq!(notification.route.id(), Some(CHANNEL_ID));
+            assert!(notification.is_new());
+        }
+        event => panic!("unexpected event: {event:?}"),
+    }
+}
+
+#[tokio::test]
+async fn updated_video_is_filtered_with_new_only() {
+    let mut harness = Harness::start(true);
+    harness.listener.set_secret(CHANNEL_ID, "secret");
+
+    let feed = feed("dQw4w9WgXcQ", "Updated", OLD_PUBLISHED, NEW_UPDATED);
+    harness.publish(CHANNEL_ID, "secret", &feed).await;
+
+    harness.assert_silent().await;
+}
+
+#[tokio::test]
+async fn updated_video_is_notified_without_new_only() {
+    let mut harness = Harness::start(false);
+    harness.listener.set_secret(CHANNEL_ID, "secret");
+
+    let feed = feed("dQw4w9WgXcQ", "Updated", OLD_PUBLISHED, NEW_UPDATED);
+    harness.publish(CHANNEL_ID, "secret", &feed).await;
+
+    match harness.recv().await.unwrap() {
+        Event::Published(notification) => assert!(!notification.is_new()),
+        event => panic!("unexpected event: {event:?}"),
+    }
+}
+
+#[tokio::test]
+async fn forged_content_is_rejected() {
+    let mut harness = Harness::start(false);
+    harness.listener.set_secret(CHANNEL_ID, "secret");
+
+    let feed = feed("dQw4w9WgXcQ", "Forged", NEW_PUBLISHED, NEW_UPDATED);
+    harness.publish(CHANNEL_ID, "not the secret", &feed).await;
+
+    assert!(matches!(
+        harness.recv().await,
+        Err(Error::Signature(SignatureError::Mismatch))
+    ));
+}
+
+#[tokio::test]
+async fn content_for_unknown_subscription_is_rejected() {
+    let mut harness = Harness::start(false);
+
+    let feed = feed("dQw4w9WgXcQ", "Unknown", NEW_PUBLISHED, NEW_UPDATED);
+    harness.publish(CHANNEL_ID, "secret", &feed).await;
+
+    assert!(matches!(
+        harness.recv().await,
+        Err(Error::Signature(SignatureError::UnknownSubscription(id))) if id == CHANNEL_ID
+    ));
+}
+
+#[tokio::test]
+async fn malformed_header() {
+    let mut harness = Harness::start(false);
+
+    harness
+        .send(b"POST /youtube/UC1 HTTP/1.1\r\nContent-Length 12\r\n\r\n")
+        .await;
+
+    assert!(matches!(
+        harness.recv().await,
+        Err(Error::Parse(ParseError::HeaderError(_)))
+    ));
+}
+
+#[tokio::test]
+async fn unknown_route() {
+    let mut harness = Harness::start(false);
+
+    let response = harness.send(b"GET /index.html HTTP/1.1\r\n\r\n").await;
+
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(matches!(
+        harness.recv().await,
+        Err(Error::Parse(ParseError::UriError))
+    ));
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use std::time::Duration;
+
+use brzthook::prelude::*;
+use common::{verification, Harness, CHANNEL_ID};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+#[tokio::test]
+async fn listen_twice() {
+    let harness = Harness::start(false);
+
+    assert!(matches!(
+        harness.listener.listen(),
+        Err(Error::AlreadyListening)
+    ));
+}
+
+#[tokio::test]
+async fn shutdown_drains_connections_in_flight() {
+    let Harness { listener, rx, addr } = &mut Harness::requested(CHANNEL_ID, Mode::Subscribe).await;
+
+    // Send the head of a verification, the rest comes during the shutdown
+    let request = verification(CHANNEL_ID, "subscribe", "abc");
+    let (head, tail) = request.split_at(request.find("&hub.challenge").unwrap());
+    let mut stream = TcpStream::connect(*addr).await.unwrap();
+    stream.write_all(head.as_bytes()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let finish = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stream.write_all(tail.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    };
+    let ((), response) = tokio::join!(listener.shutdown(), finish);
+
+    assert!(response.ends_with("\r\n\r\nabc"));
+    assert!(matches!(
+        rx.recv().await,
+        Some(Ok(Event::Verified { id: Some(id), .. })) if id == CHANNEL_ID
+    ));
+    // The loop is done with the receiver
+    assert!(rx.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn restart_after_shutdown() {
+    let mut harness = Harness::requested(CHANNEL_ID, Mode::Subscribe).await;
+    harness.listener.shutdown().await;
+
+    // Not accepted while the listener is stopped
+    let request = verification(CHANNEL_ID, "subscribe", "abc");
+    let mut pending = tokio::spawn(common::send(harness.addr, request.into_bytes()));
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), &mut pending)
+            .await
+            .is_err()
+    );
+
+    harness.rx = harness.listener.listen().unwrap();
+    assert!(pending.await.unwrap().ends_with("\r\n\r\nabc"));
+    assert!(matches!(harness.recv().await, Ok(Event::Verified { .. })));
+}
+
+#[tokio::test]
+async fn rebind() {
+    let mut harness = Harness::requested(CHANNEL_ID, Mode::Subscribe).await;
+    let old_addr = harness.addr;
+
+    harness.listener.rebind("127.0.0.1", 0).await.unwrap();
+    harness.addr = harness.listener.local_addr().unwrap();
+    harness.rx = harness.listener.listen().unwrap();
+
+    assert_ne!(harness.addr, old_addr);
+    assert!(TcpStream::connect(old_addr).await.is_err());
+    harness.verify(CHANNEL_ID, "subscribe", "abc").await;
+    assert!(matches!(harness.recv().await, Ok(Event::Verified { .. })));
+}
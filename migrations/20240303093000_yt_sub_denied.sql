@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE yt_sub ADD COLUMN denied_reason TEXT;
@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS yt_announcement (
+    yt_channel_id TEXT NOT NULL,
+    video_id TEXT NOT NULL,
+    post_channel_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    announced_on DATETIME NOT NULL,
+    PRIMARY KEY (video_id, post_channel_id)
+);
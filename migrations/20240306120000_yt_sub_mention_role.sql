@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE yt_sub ADD COLUMN mention_role_id INTEGER;
@@ -0,0 +1,8 @@
+-- Add migration script here
+ALTER TABLE yt_sub ADD COLUMN announce_uploads BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE yt_sub ADD COLUMN announce_shorts BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE yt_sub ADD COLUMN announce_premieres BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE yt_sub ADD COLUMN announce_lives BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE yt_sub ADD COLUMN template_short TEXT;
+ALTER TABLE yt_sub ADD COLUMN template_premiere TEXT;
+ALTER TABLE yt_sub ADD COLUMN template_live TEXT;
@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS yt_video (
+    yt_channel_id TEXT NOT NULL,
+    video_id TEXT NOT NULL,
+    seen_on DATETIME NOT NULL,
+    PRIMARY KEY (yt_channel_id, video_id)
+);
\ No newline at end of file
@@ -0,0 +1,41 @@
+-- Add migration script here
+-- The hub subscription is shared by every guild subscribed to a Youtube channel,
+-- while each guild may post it in several channels with their own settings
+CREATE TABLE IF NOT EXISTS yt_channel (
+    yt_channel_id TEXT PRIMARY KEY NOT NULL,
+    yt_channel_name TEXT NOT NULL,
+    expire_on DATETIME NOT NULL,
+    hub_secret TEXT,
+    denied_reason TEXT
+);
+
+INSERT INTO yt_channel (yt_channel_id, yt_channel_name, expire_on, hub_secret, denied_reason)
+    SELECT yt_channel_id, MAX(yt_channel_name), MAX(expire_on), MAX(hub_secret), MAX(denied_reason)
+    FROM yt_sub GROUP BY yt_channel_id;
+
+CREATE TABLE IF NOT EXISTS yt_sub_target (
+    yt_channel_id TEXT NOT NULL,
+    guild_id INTEGER NOT NULL,
+    post_channel_id INTEGER NOT NULL,
+    template TEXT,
+    mention_role_id INTEGER,
+    announce_uploads BOOLEAN NOT NULL DEFAULT TRUE,
+    announce_shorts BOOLEAN NOT NULL DEFAULT TRUE,
+    announce_premieres BOOLEAN NOT NULL DEFAULT TRUE,
+    announce_lives BOOLEAN NOT NULL DEFAULT TRUE,
+    template_short TEXT,
+    template_premiere TEXT,
+    template_live TEXT,
+    PRIMARY KEY (guild_id, yt_channel_id, post_channel_id),
+    FOREIGN KEY(guild_id) REFERENCES guilds(id),
+    FOREIGN KEY(yt_channel_id) REFERENCES yt_channel(yt_channel_id)
+);
+
+INSERT INTO yt_sub_target
+    SELECT yt_channel_id, guild_id, post_channel_id, template, mention_role_id,
+        announce_uploads, announce_shorts, announce_premieres, announce_lives,
+        template_short, template_premiere, template_live
+    FROM yt_sub;
+
+DROP TABLE yt_sub;
+ALTER TABLE yt_sub_target RENAME TO yt_sub;
@@ -0,0 +1,5 @@
+-- Add migration script here
+ALTER TABLE yt_channel ADD COLUMN verified_on DATETIME;
+ALTER TABLE yt_channel ADD COLUMN notified_on DATETIME;
+ALTER TABLE yt_channel ADD COLUMN renew_error TEXT;
+ALTER TABLE yt_channel ADD COLUMN alerted_on DATETIME;
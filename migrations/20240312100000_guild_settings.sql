@@ -0,0 +1,9 @@
+-- Xp parameters of each guild, the defaults are the former constants
+CREATE TABLE IF NOT EXISTS guild_settings (
+    guild_id INTEGER PRIMARY KEY,
+    min_xp_gain INTEGER NOT NULL DEFAULT 15,
+    max_xp_gain INTEGER NOT NULL DEFAULT 25,
+    spam_delay INTEGER NOT NULL DEFAULT 60,
+    xp_multiplier REAL NOT NULL DEFAULT 1.0,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id)
+);
@@ -0,0 +1,9 @@
+-- Roles given to the members reaching a level
+CREATE TABLE IF NOT EXISTS level_rewards (
+    guild_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    level INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, role_id) FOREIGN KEY (guild_id) REFERENCES guilds(id)
+);
+-- Keep the lower reward roles on level up, or replace them with the new one
+ALTER TABLE guild_settings ADD COLUMN stack_rewards BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,13 @@
+-- Multipliers of the xp gained in a channel or by a role, 0 means no xp
+CREATE TABLE IF NOT EXISTS xp_channels (
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    multiplier REAL NOT NULL,
+    PRIMARY KEY (guild_id, channel_id) FOREIGN KEY (guild_id) REFERENCES guilds(id)
+);
+CREATE TABLE IF NOT EXISTS xp_roles (
+    guild_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    multiplier REAL NOT NULL,
+    PRIMARY KEY (guild_id, role_id) FOREIGN KEY (guild_id) REFERENCES guilds(id)
+);
@@ -0,0 +1,3 @@
+-- Part of the xp earned in voice channels, xp stays the total
+ALTER TABLE levels ADD COLUMN voice_xp INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE guild_settings ADD COLUMN voice_xp_per_minute INTEGER NOT NULL DEFAULT 2;
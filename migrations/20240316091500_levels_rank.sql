@@ -0,0 +1,3 @@
+-- Ranks are computed when read, from the xp of the guild members
+ALTER TABLE levels DROP COLUMN rank;
+CREATE INDEX IF NOT EXISTS levels_guild_xp ON levels (guild_id, xp DESC);
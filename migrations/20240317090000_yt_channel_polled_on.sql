@@ -0,0 +1,2 @@
+-- Last fetch of the channel feed, when the feeds are polled
+ALTER TABLE yt_channel ADD COLUMN polled_on DATETIME;
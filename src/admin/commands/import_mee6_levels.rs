@@ -63,6 +63,7 @@ pub async fn import_mee6_levels(ctx: Context<'_>) -> Result<(), Error> {
                 level,
                 rank,
                 last_message,
+                voice_xp: 0,
             }
         })
         .collect();
@@ -70,5 +71,7 @@ pub async fn import_mee6_levels(ctx: Context<'_>) -> Result<(), Error> {
     let db = &ctx.data().db;
     levels::queries::import_from_mee6(db, user_levels, guild_id).await?;
 
+    ctx.say("Levels imported, use /levels rewards sync to update the reward roles")
+        .await?;
     Ok(())
 }
@@ -1,10 +1,12 @@
 pub mod import_mee6_levels;
+pub mod reload_listener;
 pub mod set_xp;
 pub mod shutdown;
 
 use crate::{Data, Error};
 
 pub use import_mee6_levels::import_mee6_levels;
+pub use reload_listener::reload_listener;
 pub use set_xp::set_xp;
 pub use shutdown::shutdown;
 
@@ -0,0 +1,31 @@
+use tracing::{info, instrument};
+
+use crate::{config::Config, youtube, Context, Error};
+
+/// Restart the webhook listener on the address set in the config file
+///
+/// Only the address and port are reloaded. Nothing is done in `poll` mode, where the bot
+/// does not listen.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, owners_only, hide_in_help, ephemeral)]
+pub async fn reload_listener(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    if !ctx.data().config.brzthook.mode.push() {
+        ctx.say("The webhook listener is not used in poll mode")
+            .await?;
+        return Ok(());
+    }
+
+    let config = Config::load()?;
+    let listener = &ctx.data().hook_listener;
+    info!("Rebinding webhook listener...");
+    listener
+        .rebind(&config.brzthook.ip_addr, config.brzthook.port)
+        .await?;
+    youtube::spawn_listen_loop(ctx.serenity_context().clone(), ctx.data());
+
+    ctx.say(format!("Listening on {}", listener.local_addr()?))
+        .await?;
+    Ok(())
+}
@@ -29,6 +29,8 @@ pub async fn set_xp(
     let mut user_level = levels::queries::get_user(db, user_id.get(), guild_id.get()).await?;
     user_level.xp = xp as i64;
     user_level.level = level;
+    // The voice part cannot be more than the new total
+    user_level.voice_xp = user_level.voice_xp.min(user_level.xp);
     levels::queries::update_user(db, &user_level, guild_id.get()).await?;
 
     info!("Admin updated user {user_id} in guild {guild_id}: {xp} - {level}");
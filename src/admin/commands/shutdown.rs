@@ -6,6 +6,8 @@ use crate::{Context, Error};
 #[poise::command(slash_command, owners_only, hide_in_help, ephemeral)]
 pub async fn shutdown(ctx: Context<'_>) -> Result<(), Error> {
     ctx.say("Shutting down...").await?;
+    info!("Stopping webhook listener...");
+    ctx.data().hook_listener.shutdown().await;
     info!("Closing database...");
     ctx.data().db.close().await;
     info!("Shutting down all shards...");
@@ -1,15 +1,80 @@
 use serde::Deserialize;
 
+use crate::Error;
+
+const CONFIG_FILE: &str = "config.toml";
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub database: String,
     pub brzthook: HookCfg,
 }
 
+impl Config {
+    pub fn load() -> Result<Self, Error> {
+        let cfg_file = std::fs::read_to_string(CONFIG_FILE)?;
+        Ok(toml::from_str(&cfg_file)?)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct HookCfg {
     pub port: u32,
     pub ip_addr: String,
     pub callback: String,
+    /// Hub the subscriptions are sent to; brzthook's default hub if not set
+    #[serde(default)]
+    pub hub: Option<String>,
+    /// Callback routes, e.g. `/youtube/{id}`; the callback URL is used as is if empty
+    #[serde(default)]
+    pub routes: Vec<String>,
     pub new_only: bool,
+    /// Edit the announcement of a video when it is updated, instead of ignoring the update;
+    /// needs `new_only = false` for the updates to reach the bot
+    #[serde(default)]
+    pub edit_on_update: bool,
+    /// How new videos are found
+    #[serde(default)]
+    pub mode: HookMode,
+    /// Seconds between two fetches of the channel feeds, in `poll` and `both` modes
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+    /// Discord channel alerted when a subscription fails or goes stale
+    #[serde(default)]
+    pub log_channel: Option<u64>,
+    /// Days without notification from the hub, or without fetching the feed in poll mode,
+    /// before a subscription is reported as stale
+    #[serde(default = "default_stale_days")]
+    pub stale_days: i64,
+}
+
+fn default_poll_interval() -> u64 {
+    15 * 60
+}
+
+fn default_stale_days() -> i64 {
+    7
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookMode {
+    /// Notifications pushed by the hub to the webhook listener
+    #[default]
+    Push,
+    /// Periodic fetch of the RSS feed of every subscribed channel, for a bot the hub
+    /// cannot reach
+    Poll,
+    /// Both, the feeds catching up on what the hub did not deliver
+    Both,
+}
+
+impl HookMode {
+    pub fn push(self) -> bool {
+        matches!(self, Self::Push | Self::Both)
+    }
+
+    pub fn poll(self) -> bool {
+        matches!(self, Self::Poll | Self::Both)
+    }
 }
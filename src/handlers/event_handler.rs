@@ -5,7 +5,7 @@ use poise::serenity_prelude::{self as serenity, parse_message_url};
 use std::{mem, sync::Arc, time::Instant};
 use tracing::{debug, error, info, instrument, trace};
 
-use crate::{database, youtube, Context, Data, Error};
+use crate::{database, levels, youtube, Context, Data, Error};
 
 #[instrument(skip_all)]
 pub async fn on_event(
@@ -33,24 +33,17 @@ pub async fn on_event(
                 info!("Permissions: {:#?}", permissions);
             }
 
-            // Starts the listener in a separate thread
-            let db_c = Arc::clone(db);
-            let serenity_ctx = ctx.clone();
-            let listener = Arc::clone(&user_data.hook_listener);
-            std::thread::spawn(move || {
-                if let Err(e) = youtube::listen_loop(serenity_ctx, db_c, listener) {
-                    error!("in listen_loop: {e}");
-                }
-            });
-
-            // Starts the expiration checker
-            let db_c = Arc::clone(db);
-            let listener = Arc::clone(&user_data.hook_listener);
-            std::thread::spawn(move || {
-                if let Err(e) = youtube::expiration_check_timer(listener, db_c) {
-                    error!("in expiration_check_timer: {e}");
-                }
-            });
+            let mode = user_data.config.brzthook.mode;
+            if mode.push() {
+                // Starts the listener in a separate task
+                youtube::spawn_listen_loop(ctx.clone(), user_data);
+            }
+            if mode.poll() {
+                youtube::spawn_poll_loop(ctx.clone(), user_data);
+            }
+
+            // Starts the expiration checker, which only renews in push mode
+            youtube::spawn_expiration_check(ctx.clone(), user_data);
         }
 
         serenity::FullEvent::Message { new_message } => {
@@ -88,6 +81,11 @@ pub async fn on_event(
             info!("Member removed: {}", user.name);
             member::member_removal_handler(guild_id, user, ctx).await?;
         }
+
+        serenity::FullEvent::VoiceStateUpdate { old, new } => {
+            trace!("Voice state updated: {}", new.user_id);
+            levels::func::voice_xp::voice_state_update(ctx, user_data, old.as_ref(), new).await?;
+        }
         _ => {}
     }
 
@@ -50,7 +50,13 @@ pub async fn message_handler(
     let t_0 = Instant::now();
     let db = &user_data.db;
     database::add_user(db, user_id.get()).await?;
-    levels::func::message_xp::add_xp(ctx, user_data, &guild_id, &channel_id, &user_id).await?;
+    let roles = new_message
+        .member
+        .as_ref()
+        .map(|member| member.roles.clone())
+        .unwrap_or_default();
+    levels::func::message_xp::add_xp(ctx, user_data, &guild_id, &channel_id, &user_id, &roles)
+        .await?;
     debug!("add_xp finished in {} µs", t_0.elapsed().as_micros());
 
     Ok(())
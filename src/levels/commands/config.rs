@@ -0,0 +1,127 @@
+use tracing::{info, instrument};
+
+use super::{func::xp_settings, models::XpSettings};
+use crate::{Context, Error};
+
+const MAX_XP_GAIN: i64 = 1000;
+const MAX_SPAM_DELAY: i64 = 24 * 3600;
+const MAX_MULTIPLIER: f64 = 10.0;
+const MAX_VOICE_XP: i64 = 100;
+
+/// Set the xp gained per message and in voice channels
+///
+/// Without argument, shows the current settings. The xp gained is a random amount between
+/// min_xp and max_xp, times the multiplier, once every cooldown seconds at most.
+///
+/// voice_xp is gained for each minute spent in a voice channel with other members, while not
+/// muted or deafened. 0 disables the voice xp.
+///
+/// stack_rewards keeps the reward roles of the lower levels when a new one is given.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, guild_only, ephemeral, category = "Levels")]
+pub async fn config(
+    ctx: Context<'_>,
+    #[description = "Least xp gained per message"]
+    #[min = 0]
+    #[max = 1000]
+    min_xp: Option<i64>,
+    #[description = "Most xp gained per message"]
+    #[min = 0]
+    #[max = 1000]
+    max_xp: Option<i64>,
+    #[description = "Seconds before a new message gains xp"]
+    #[min = 0]
+    #[max = 86400]
+    cooldown: Option<i64>,
+    #[description = "Multiplier of the xp gained, e.g. 2 for double xp"]
+    #[min = 0]
+    #[max = 10]
+    multiplier: Option<f64>,
+    #[description = "Xp gained per minute in a voice channel"]
+    #[min = 0]
+    #[max = 100]
+    voice_xp: Option<i64>,
+    #[description = "Keep the lower reward roles on level up, instead of replacing them"]
+    stack_rewards: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
+    let db = &ctx.data().db;
+    let cache = &ctx.data().xp_settings;
+
+    let mut settings = xp_settings::get_xp_settings(db, cache, guild_id.get()).await?;
+    if min_xp.is_none()
+        && max_xp.is_none()
+        && cooldown.is_none()
+        && multiplier.is_none()
+        && voice_xp.is_none()
+        && stack_rewards.is_none()
+    {
+        ctx.say(describe(&settings)).await?;
+        return Ok(());
+    }
+
+    settings.min_xp_gain = min_xp.unwrap_or(settings.min_xp_gain);
+    settings.max_xp_gain = max_xp.unwrap_or(settings.max_xp_gain);
+    settings.spam_delay = cooldown.unwrap_or(settings.spam_delay);
+    settings.multiplier = multiplier.unwrap_or(settings.multiplier);
+    settings.voice_xp_per_minute = voice_xp.unwrap_or(settings.voice_xp_per_minute);
+    settings.stack_rewards = stack_rewards.unwrap_or(settings.stack_rewards);
+    if let Err(e) = validate(&settings) {
+        ctx.say(e).await?;
+        return Ok(());
+    }
+
+    xp_settings::set_xp_settings(db, cache, guild_id.get(), &settings).await?;
+    info!("Xp settings of guild {guild_id} set to {settings:?}");
+
+    ctx.say(format!("Xp settings updated\n{}", describe(&settings)))
+        .await?;
+    Ok(())
+}
+
+/// Check the settings, including the ones not given in the command
+fn validate(settings: &XpSettings) -> Result<(), String> {
+    if !(0..=MAX_XP_GAIN).contains(&settings.min_xp_gain)
+        || !(0..=MAX_XP_GAIN).contains(&settings.max_xp_gain)
+    {
+        return Err(format!("The xp gained must be between 0 and {MAX_XP_GAIN}"));
+    }
+    if settings.min_xp_gain > settings.max_xp_gain {
+        return Err(format!(
+            "min_xp ({}) cannot be more than max_xp ({})",
+            settings.min_xp_gain, settings.max_xp_gain
+        ));
+    }
+    if !(0..=MAX_SPAM_DELAY).contains(&settings.spam_delay) {
+        return Err(format!(
+            "The cooldown must be between 0 and {MAX_SPAM_DELAY} seconds"
+        ));
+    }
+    if !(0.0..=MAX_MULTIPLIER).contains(&settings.multiplier) {
+        return Err(format!(
+            "The multiplier must be between 0 and {MAX_MULTIPLIER}"
+        ));
+    }
+    if !(0..=MAX_VOICE_XP).contains(&settings.voice_xp_per_minute) {
+        return Err(format!(
+            "The voice xp must be between 0 and {MAX_VOICE_XP} per minute"
+        ));
+    }
+    Ok(())
+}
+
+fn describe(settings: &XpSettings) -> String {
+    let rewards = if settings.stack_rewards {
+        "stacked"
+    } else {
+        "replaced"
+    };
+    format!(
+        "Xp per message: {} to {}\nCooldown: {}s\nVoice xp per minute: {}\nMultiplier: x{}\nReward roles: {rewards}",
+        settings.min_xp_gain,
+        settings.max_xp_gain,
+        settings.spam_delay,
+        settings.voice_xp_per_minute,
+        settings.multiplier
+    )
+}
@@ -1,12 +1,33 @@
+pub mod config;
+pub mod multipliers;
 pub mod rank;
+pub mod rewards;
 pub mod top;
 
+use tracing::instrument;
+
 use super::{constants, draw, func, models, queries};
-use crate::{Data, Error};
+use crate::{Context, Data, Error};
 
+pub use config::config;
+pub use multipliers::multipliers;
 pub use rank::rank;
+pub use rewards::rewards;
 pub use top::top;
 
+/// Configure the levels (require ADMINISTRATOR permission)
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    category = "Levels",
+    subcommands("config", "rewards", "multipliers")
+)]
+pub async fn levels(ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
 pub fn all() -> Vec<poise::Command<Data, Error>> {
-    vec![rank::rank(), top::top()]
+    vec![levels(), rank::rank(), top::top()]
 }
@@ -0,0 +1,117 @@
+use poise::serenity_prelude::{self as serenity, Mentionable};
+use tracing::{info, instrument};
+
+use super::func::xp_settings;
+use crate::{Context, Error};
+
+/// Set the xp multiplier of channels and roles
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Levels",
+    subcommands("channel", "role", "list")
+)]
+pub async fn multipliers(ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Multiply the xp gained in a channel
+///
+/// 0 gives no xp in the channel, 1 removes the multiplier.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, guild_only, ephemeral, category = "Levels")]
+async fn channel(
+    ctx: Context<'_>,
+    #[description = "Channel"]
+    #[channel_types("Text", "News", "Voice")]
+    channel: serenity::ChannelId,
+    #[description = "Multiplier, e.g. 2 for double xp or 0 for none"]
+    #[min = 0]
+    #[max = 10]
+    multiplier: f64,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
+    let multiplier = (multiplier != 1.0).then_some(multiplier);
+
+    xp_settings::set_channel_multiplier(
+        &ctx.data().db,
+        &ctx.data().xp_settings,
+        guild_id.get(),
+        channel,
+        multiplier,
+    )
+    .await?;
+    info!("Xp multiplier of channel {channel} set to {multiplier:?}");
+
+    ctx.say(describe(channel.mention(), multiplier)).await?;
+    Ok(())
+}
+
+/// Multiply the xp gained by the members with a role
+///
+/// 0 gives no xp to the role, 1 removes the multiplier. A member with several roles gets the
+/// best multiplier, unless one of them gives no xp.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, guild_only, ephemeral, category = "Levels")]
+async fn role(
+    ctx: Context<'_>,
+    #[description = "Role"] role: serenity::Role,
+    #[description = "Multiplier, e.g. 2 for double xp or 0 for none"]
+    #[min = 0]
+    #[max = 10]
+    multiplier: f64,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
+    let multiplier = (multiplier != 1.0).then_some(multiplier);
+
+    xp_settings::set_role_multiplier(
+        &ctx.data().db,
+        &ctx.data().xp_settings,
+        guild_id.get(),
+        role.id,
+        multiplier,
+    )
+    .await?;
+    info!("Xp multiplier of role {} set to {multiplier:?}", role.id);
+
+    ctx.say(describe(role.mention(), multiplier)).await?;
+    Ok(())
+}
+
+/// List the xp multipliers of the channels and roles
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, guild_only, ephemeral, category = "Levels")]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
+    let settings =
+        xp_settings::get_xp_settings(&ctx.data().db, &ctx.data().xp_settings, guild_id.get())
+            .await?;
+
+    let mut lines = settings
+        .channel_multipliers
+        .iter()
+        .map(|(channel_id, multiplier)| describe(channel_id.mention(), Some(*multiplier)))
+        .chain(
+            settings
+                .role_multipliers
+                .iter()
+                .map(|(role_id, multiplier)| describe(role_id.mention(), Some(*multiplier))),
+        )
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        lines.push("No channel or role multiplier".to_string());
+    }
+    lines.push(format!("Guild multiplier: x{}", settings.multiplier));
+
+    ctx.say(lines.join("\n")).await?;
+    Ok(())
+}
+
+fn describe(target: serenity::Mention, multiplier: Option<f64>) -> String {
+    match multiplier {
+        None => format!("{target}: normal xp"),
+        Some(0.0) => format!("{target}: no xp"),
+        Some(multiplier) => format!("{target}: x{multiplier}"),
+    }
+}
@@ -76,7 +76,13 @@ pub async fn rank(
 
     let t_2 = Instant::now();
     let file = serenity::CreateAttachment::bytes(image.as_slice(), "rank_card.png");
-    ctx.send(CreateReply::default().attachment(file)).await?;
+    let split = format!(
+        "Text xp: {} · Voice xp: {}",
+        user_level.xp - user_level.voice_xp,
+        user_level.voice_xp
+    );
+    ctx.send(CreateReply::default().content(split).attachment(file))
+        .await?;
     info!("Rank card sent in {} µs", t_2.elapsed().as_micros());
 
     info!("Command rank processed in {} µs", t_0.elapsed().as_micros());
@@ -0,0 +1,156 @@
+use poise::serenity_prelude::{self as serenity, Mentionable};
+use tracing::{info, instrument, warn};
+
+use super::{
+    func::{rewards::update_member_rewards, xp_settings},
+    queries,
+};
+use crate::{Context, Error};
+
+/// Manage the roles given on level up
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Levels",
+    subcommands("add", "remove", "list", "sync")
+)]
+pub async fn rewards(ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Give a role to the members reaching a level
+///
+/// Members already above the level get it with /levels rewards sync.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, guild_only, ephemeral, category = "Levels")]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Level to reach"]
+    #[min = 1]
+    level: i64,
+    #[description = "Role given"] role: serenity::Role,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
+    if role.id.get() == guild_id.get() || role.managed {
+        ctx.say(format!("{} cannot be given by the bot", role.name))
+            .await?;
+        return Ok(());
+    }
+
+    queries::set_reward(&ctx.data().db, guild_id.get(), role.id.get(), level).await?;
+    info!(
+        "Role {} given at level {level} in guild {guild_id}",
+        role.id
+    );
+
+    ctx.say(format!("{} will be given at level {level}", role.mention()))
+        .await?;
+    Ok(())
+}
+
+/// Stop giving a role on level up
+///
+/// The members keep the role until /levels rewards sync.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, guild_only, ephemeral, category = "Levels")]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Role given"] role: serenity::Role,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
+
+    let deleted = queries::delete_reward(&ctx.data().db, guild_id.get(), role.id.get()).await?;
+    let content = if deleted {
+        format!("{} is not a reward anymore", role.mention())
+    } else {
+        format!("{} is not a reward", role.mention())
+    };
+    ctx.say(content).await?;
+    Ok(())
+}
+
+/// List the roles given on level up
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, guild_only, ephemeral, category = "Levels")]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
+    let db = &ctx.data().db;
+
+    let rewards = queries::get_rewards(db, guild_id.get()).await?;
+    if rewards.is_empty() {
+        ctx.say("No reward role, add one with /levels rewards add")
+            .await?;
+        return Ok(());
+    }
+
+    let settings =
+        xp_settings::get_xp_settings(db, &ctx.data().xp_settings, guild_id.get()).await?;
+    let mode = if settings.stack_rewards {
+        "The roles of the lower levels are kept"
+    } else {
+        "The roles of the lower levels are replaced"
+    };
+    let lines = rewards
+        .iter()
+        .map(|reward| format!("Level {}: {}", reward.level, reward.role_id.mention()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.say(format!("{lines}\n\n{mode}")).await?;
+    Ok(())
+}
+
+/// Give every member the reward roles of their level, and take away the others
+///
+/// Useful after changing the rewards or importing the levels.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, guild_only, ephemeral, category = "Levels")]
+async fn sync(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
+    let db = &ctx.data().db;
+
+    let rewards = queries::get_rewards(db, guild_id.get()).await?;
+    if rewards.is_empty() {
+        ctx.say("No reward role, add one with /levels rewards add")
+            .await?;
+        return Ok(());
+    }
+    let settings =
+        xp_settings::get_xp_settings(db, &ctx.data().xp_settings, guild_id.get()).await?;
+    let users = queries::get_all_users(db, guild_id.get()).await?;
+
+    let mut updated = 0;
+    let mut failed = 0;
+    for user in users {
+        // Members who left the guild are still in the levels
+        let Ok(member) = guild_id.member(ctx, user.user_id).await else {
+            continue;
+        };
+        let synced = update_member_rewards(
+            ctx.http(),
+            &member,
+            &rewards,
+            user.level,
+            settings.stack_rewards,
+        )
+        .await;
+        match synced {
+            Ok(_) => updated += 1,
+            Err(e) => {
+                warn!("Cannot sync the reward roles of {}: {e}", user.user_id);
+                failed += 1;
+            }
+        }
+    }
+
+    let mut content = format!("Reward roles synced for {updated} members");
+    if failed > 0 {
+        content.push_str(&format!(
+            "\n{failed} members could not be updated, check that my role is above the rewards"
+        ));
+    }
+    ctx.say(content).await?;
+    Ok(())
+}
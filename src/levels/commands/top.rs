@@ -28,17 +28,14 @@ pub async fn top(
     let (guild_id, guild_name) = (guild.id.get(), guild.name.as_str());
 
     let t_1 = Instant::now();
-    // Get a vec of all users in database
+    // Get the best ranked users in database
     let db = &ctx.data().db;
-    let mut all_users = queries::get_all_users(db, guild_id).await?;
-    debug!("Got all_users in {} µs", t_1.elapsed().as_micros());
+    let users = queries::get_top_users(db, guild_id, number as i64).await?;
+    debug!("Got top users in {} µs", t_1.elapsed().as_micros());
 
     let t_2 = Instant::now();
-    // Sort all users by rank
-    all_users.sort_by(|a, b| a.rank.cmp(&b.rank));
-
     let mut top_users = vec![];
-    for user in all_users.iter().take(number) {
+    for user in &users {
         let name = ctx
             .http()
             .get_member(guild_id.into(), user.user_id)
@@ -1,7 +1,8 @@
-// Xp parameters
+// Default xp parameters of a guild, changed with /levels config
 pub const MIN_XP_GAIN: i64 = 15;
 pub const MAX_XP_GAIN: i64 = 25;
 pub const DELAY_ANTI_SPAM: i64 = 60;
+pub const VOICE_XP_PER_MINUTE: i64 = 2;
 
 // Rank card constants
 pub const CARD_FONT: &str = "Akira Expanded"; // Font needs to be installed on the system (https://www.dafont.com/akira-expanded.font)
@@ -1,9 +1,9 @@
 use poise::serenity_prelude as serenity;
 use std::time::Instant;
-use tracing::{debug, info, instrument};
+use tracing::{debug, info, instrument, warn};
 
-use super::queries;
-use crate::{Data, Db, Error};
+use super::{queries, rewards, xp_settings};
+use crate::{levels::models::XpSettings, Data, Db, Error};
 
 #[instrument(skip_all)]
 pub async fn add_xp(
@@ -12,65 +12,84 @@ pub async fn add_xp(
     guild_id: &serenity::GuildId,
     channel_id: &serenity::ChannelId,
     user_id: &serenity::UserId,
+    roles: &[serenity::RoleId],
 ) -> Result<(), Error> {
     let db = &user_data.db;
+    let settings = xp_settings::get_xp_settings(db, &user_data.xp_settings, guild_id.get()).await?;
+
+    // No xp in the excluded channels or for the excluded roles, and the message does not
+    // count for the spam delay either
+    let multiplier = settings.multiplier_for(*channel_id, roles);
+    if multiplier == 0.0 {
+        debug!("No xp in {channel_id} with roles {roles:?}");
+        return Ok(());
+    }
+
     let mut user = queries::get_user(db, user_id.get(), guild_id.get()).await?;
 
     // User gain xp if the time defined by spam_delay parameter in xp_settings
     // has passed since his last message
-    let has_gained_xp = user.gain_xp_if_not_spam();
+    let has_gained_xp = user.gain_xp_if_not_spam(&settings, multiplier);
 
     // Update user in database with new xp and level
     if has_gained_xp {
         info!("User has gained XP");
-        // Increment level of the user if enough xp, then send a chat message
-        if user.has_level_up() {
-            info!("User has levelled up");
-            let mention = serenity::Mention::from(*user_id);
-            let message = format!("Level Up, {mention}!");
-            channel_id
-                .send_message(&ctx.http, serenity::CreateMessage::new().content(&message))
-                .await?;
-        }
+        // Increment level of the user if enough xp
+        let has_level_up = user.has_level_up();
 
         let t_0 = Instant::now();
         queries::update_user(db, &user, guild_id.get()).await?;
         debug!("Updated user : {user:#?}");
         debug!("update_user finished in {} µs", t_0.elapsed().as_micros());
 
-        let t_1 = Instant::now();
-        // Recalculate ranking of the user in the guild
-        update_users_ranks(db, guild_id.get()).await?;
-        debug!("Updated ranks");
-        debug!(
-            "update_users_ranks finished in {} µs",
-            t_1.elapsed().as_micros()
-        );
+        // The xp is saved even if the bot cannot post in the channel
+        if has_level_up {
+            info!("User has levelled up");
+            let message = level_up(ctx, db, &settings, *guild_id, *user_id, user.level).await;
+            let sent = channel_id
+                .send_message(&ctx.http, serenity::CreateMessage::new().content(&message))
+                .await;
+            if let Err(e) = sent {
+                warn!("Cannot send the level up message in {channel_id}: {e}");
+            }
+        }
     }
 
     Ok(())
 }
 
-#[allow(clippy::cast_possible_wrap)]
-#[instrument(skip_all)]
-async fn update_users_ranks(db: &Db, guild_id: u64) -> Result<(), Error> {
-    // Get a Vec of all users in database
-    let mut all_users = queries::get_all_users(db, guild_id).await?;
-
-    // Sort user by descendant xp
-    all_users.sort_by(|a, b| b.xp.cmp(&a.xp));
+/// Give the reward roles of the new `level`, and return the level up message
+pub(super) async fn level_up(
+    ctx: &serenity::Context,
+    db: &Db,
+    settings: &XpSettings,
+    guild_id: serenity::GuildId,
+    user_id: serenity::UserId,
+    level: i64,
+) -> String {
+    let mention = serenity::Mention::from(user_id);
+    let mut message = format!("Level Up, {mention}!");
 
-    let mut rank_has_changed = vec![];
-    for (i, user) in &mut all_users.iter_mut().enumerate() {
-        if user.rank != i as i64 + 1 {
-            user.rank = i as i64 + 1;
-            rank_has_changed.push(*user);
+    // A missing permission must not prevent the xp from being saved
+    let stack = settings.stack_rewards;
+    match rewards::reward_level_up(ctx, db, guild_id, user_id, level, stack).await {
+        Ok(role_ids) => {
+            let names = guild_id
+                .to_guild_cached(ctx)
+                .map(|guild| {
+                    role_ids
+                        .iter()
+                        .filter_map(|role_id| guild.roles.get(role_id))
+                        .map(|role| format!("**{}**", role.name))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            if !names.is_empty() {
+                message.push_str(&format!("\nYou are now {}", names.join(", ")));
+            }
         }
+        Err(e) => warn!("Cannot give the reward roles: {e}"),
     }
 
-    if !rank_has_changed.is_empty() {
-        queries::update_ranks(db, rank_has_changed, guild_id).await?;
-    }
-
-    Ok(())
+    message
 }
@@ -1,5 +1,8 @@
 pub mod message_xp;
 pub mod resize_avatar;
+pub mod rewards;
+pub mod voice_xp;
 pub mod xp_func;
+pub mod xp_settings;
 
 use super::queries;
\ No newline at end of file
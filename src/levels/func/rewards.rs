@@ -0,0 +1,122 @@
+use poise::serenity_prelude::{self as serenity, GuildId, RoleId, UserId};
+use tracing::{info, instrument};
+
+use super::queries;
+use crate::{database::Db, levels::models::LevelReward, Error};
+
+/// Split the reward roles into the ones a member at `level` should have and the others
+///
+/// When rewards do not stack, only the roles of the highest level reached are kept.
+pub fn reward_roles(
+    rewards: &[LevelReward],
+    level: i64,
+    stack: bool,
+) -> (Vec<RoleId>, Vec<RoleId>) {
+    let top_level = rewards
+        .iter()
+        .map(|reward| reward.level)
+        .filter(|reward_level| *reward_level <= level)
+        .max();
+
+    let (earned, others): (Vec<_>, Vec<_>) = rewards
+        .iter()
+        .partition(|reward| reward.level <= level && (stack || Some(reward.level) == top_level));
+    let role_ids = |rewards: Vec<&LevelReward>| {
+        rewards
+            .into_iter()
+            .map(|reward| reward.role_id)
+            .collect::<Vec<_>>()
+    };
+
+    (role_ids(earned), role_ids(others))
+}
+
+/// Give `member` the reward roles of `level` and take away the other reward roles
+///
+/// Returns the roles given.
+#[instrument(skip(http, member, rewards), fields(member = %member.user.id))]
+pub async fn update_member_rewards(
+    http: &serenity::Http,
+    member: &serenity::Member,
+    rewards: &[LevelReward],
+    level: i64,
+    stack: bool,
+) -> Result<Vec<RoleId>, Error> {
+    let (earned, others) = reward_roles(rewards, level, stack);
+    let to_add = earned
+        .into_iter()
+        .filter(|role_id| !member.roles.contains(role_id))
+        .collect::<Vec<_>>();
+    let to_remove = others
+        .into_iter()
+        .filter(|role_id| member.roles.contains(role_id))
+        .collect::<Vec<_>>();
+
+    if !to_add.is_empty() {
+        info!("Giving roles {to_add:?}");
+        member.add_roles(http, &to_add).await?;
+    }
+    if !to_remove.is_empty() {
+        info!("Removing roles {to_remove:?}");
+        member.remove_roles(http, &to_remove).await?;
+    }
+
+    Ok(to_add)
+}
+
+/// Update the reward roles of a member who reached `level`
+///
+/// Returns the roles given.
+#[instrument(skip(ctx, db))]
+pub async fn reward_level_up(
+    ctx: &serenity::Context,
+    db: &Db,
+    guild_id: GuildId,
+    user_id: UserId,
+    level: i64,
+    stack: bool,
+) -> Result<Vec<RoleId>, Error> {
+    let rewards = queries::get_rewards(db, guild_id.get()).await?;
+    if rewards.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let member = guild_id.member(ctx, user_id).await?;
+    update_member_rewards(&ctx.http, &member, &rewards, level, stack).await
+}
+
+#[test]
+fn test_reward_roles() {
+    let rewards = [
+        LevelReward {
+            role_id: RoleId::new(1),
+            level: 5,
+        },
+        LevelReward {
+            role_id: RoleId::new(2),
+            level: 20,
+        },
+        LevelReward {
+            role_id: RoleId::new(3),
+            level: 20,
+        },
+    ];
+    let role_ids = |ids: &[u64]| ids.iter().map(|id| RoleId::new(*id)).collect::<Vec<_>>();
+
+    assert_eq!(
+        reward_roles(&rewards, 4, true),
+        (vec![], role_ids(&[1, 2, 3]))
+    );
+    assert_eq!(
+        reward_roles(&rewards, 5, true),
+        (role_ids(&[1]), role_ids(&[2, 3]))
+    );
+    assert_eq!(
+        reward_roles(&rewards, 25, true),
+        (role_ids(&[1, 2, 3]), vec![])
+    );
+    assert_eq!(
+        reward_roles(&rewards, 25, false),
+        (role_ids(&[2, 3]), role_ids(&[1]))
+    );
+}
@@ -0,0 +1,253 @@
+use poise::serenity_prelude::{self as serenity, ChannelId, GuildId, RoleId, UserId};
+use std::{collections::HashMap, time::Instant};
+use tracing::{debug, error, info, instrument, warn};
+
+use super::{message_xp::level_up, queries, xp_settings};
+use crate::{
+    levels::models::{VoiceSession, VoiceSessions},
+    Data, Error,
+};
+
+/// A member in a voice channel, as seen in the cache
+#[derive(Debug, Clone, Copy)]
+struct VoiceMember {
+    user_id: UserId,
+    channel_id: ChannelId,
+    is_bot: bool,
+    /// Muted or deafened, by themself or by a moderator
+    silenced: bool,
+}
+
+/// Open the voice sessions of the members who can now earn xp in the channels `old` left
+/// and `new` joined, and give the xp of the sessions that ended
+///
+/// Members earn xp while they are neither muted nor deafened, and not alone with bots.
+/// The afk channel gives no xp.
+#[instrument(skip_all, fields(user_id = %new.user_id))]
+pub async fn voice_state_update(
+    ctx: &serenity::Context,
+    user_data: &Data,
+    old: Option<&serenity::VoiceState>,
+    new: &serenity::VoiceState,
+) -> Result<(), Error> {
+    let Some(guild_id) = new.guild_id else {
+        return Ok(());
+    };
+    let channels = [old.and_then(|state| state.channel_id), new.channel_id];
+
+    // Read the members of the channels from the cache, before any await
+    let (eligible, mut roles) = {
+        let guild = guild_id.to_guild_cached(ctx).ok_or("Guild not in cache")?;
+        let afk_channel_id = guild.afk_metadata.as_ref().map(|afk| afk.afk_channel_id);
+        let voice_members = guild
+            .voice_states
+            .values()
+            .filter(|state| state.channel_id.is_some() && channels.contains(&state.channel_id))
+            .filter_map(|state| {
+                let is_bot = guild
+                    .members
+                    .get(&state.user_id)
+                    .is_some_and(|member| member.user.bot);
+                Some(VoiceMember {
+                    user_id: state.user_id,
+                    channel_id: state.channel_id?,
+                    is_bot,
+                    silenced: state.mute || state.self_mute || state.deaf || state.self_deaf,
+                })
+            })
+            .collect::<Vec<_>>();
+        let roles = voice_members
+            .iter()
+            .filter_map(|member| guild.members.get(&member.user_id))
+            .map(|member| (member.user.id, member.roles.clone()))
+            .collect::<HashMap<_, _>>();
+
+        (eligible_members(&voice_members, afk_channel_id), roles)
+    };
+    if let Some(member) = &new.member {
+        roles.insert(member.user.id, member.roles.clone());
+    }
+
+    let ended = update_sessions(
+        &user_data.voice_sessions,
+        guild_id,
+        new.user_id,
+        &channels,
+        &eligible,
+        Instant::now(),
+    );
+    // One member failing must not take the xp of the others
+    for (user_id, session) in ended {
+        let roles = roles.remove(&user_id).unwrap_or_default();
+        if let Err(e) = add_voice_xp(ctx, user_data, guild_id, user_id, session, &roles).await {
+            error!("Cannot give the voice xp of {user_id}: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Members of `voice_members` who can earn xp, with their channel
+fn eligible_members(
+    voice_members: &[VoiceMember],
+    afk_channel_id: Option<ChannelId>,
+) -> HashMap<UserId, ChannelId> {
+    let mut humans = HashMap::<ChannelId, usize>::new();
+    for member in voice_members.iter().filter(|member| !member.is_bot) {
+        *humans.entry(member.channel_id).or_default() += 1;
+    }
+
+    voice_members
+        .iter()
+        .filter(|member| !member.is_bot && !member.silenced)
+        .filter(|member| Some(member.channel_id) != afk_channel_id)
+        .filter(|member| humans.get(&member.channel_id).copied().unwrap_or_default() > 1)
+        .map(|member| (member.user_id, member.channel_id))
+        .collect()
+}
+
+/// Close the sessions of the members of `channels`, and of `user_id`, who cannot earn xp
+/// there anymore, and open the sessions of the `eligible` members
+///
+/// Returns the sessions closed.
+fn update_sessions(
+    sessions: &VoiceSessions,
+    guild_id: GuildId,
+    user_id: UserId,
+    channels: &[Option<ChannelId>],
+    eligible: &HashMap<UserId, ChannelId>,
+    now: Instant,
+) -> Vec<(UserId, VoiceSession)> {
+    let mut sessions = sessions.lock().unwrap();
+    let mut ended = vec![];
+
+    sessions.retain(|(session_guild_id, session_user_id), session| {
+        let affected = *session_guild_id == guild_id
+            && (*session_user_id == user_id || channels.contains(&Some(session.channel_id)));
+        let keep = !affected || eligible.get(session_user_id) == Some(&session.channel_id);
+        if !keep {
+            ended.push((*session_user_id, *session));
+        }
+        keep
+    });
+    for (user_id, channel_id) in eligible {
+        sessions
+            .entry((guild_id, *user_id))
+            .or_insert(VoiceSession {
+                channel_id: *channel_id,
+                since: now,
+            });
+    }
+
+    ended
+}
+
+/// Give the xp of the minutes spent in `session`
+#[instrument(skip(ctx, user_data, session))]
+async fn add_voice_xp(
+    ctx: &serenity::Context,
+    user_data: &Data,
+    guild_id: GuildId,
+    user_id: UserId,
+    session: VoiceSession,
+    roles: &[RoleId],
+) -> Result<(), Error> {
+    let minutes = session.since.elapsed().as_secs() / 60;
+    let db = &user_data.db;
+    let settings = xp_settings::get_xp_settings(db, &user_data.xp_settings, guild_id.get()).await?;
+    let multiplier = settings.multiplier_for(session.channel_id, roles);
+    if minutes == 0 || settings.voice_xp_per_minute == 0 || multiplier == 0.0 {
+        debug!("No xp for {minutes} minutes in {}", session.channel_id);
+        return Ok(());
+    }
+
+    let mut user = queries::get_user(db, user_id.get(), guild_id.get()).await?;
+    let gained = user.gain_voice_xp(minutes, &settings, multiplier);
+    info!("User has gained {gained} XP in {minutes} minutes of voice");
+
+    // Long sessions can give several levels at once
+    let old_level = user.level;
+    while user.has_level_up() {}
+    queries::update_user(db, &user, guild_id.get()).await?;
+
+    if user.level > old_level {
+        info!("User has levelled up");
+        let message = level_up(ctx, db, &settings, guild_id, user_id, user.level).await;
+        // The text chat of the voice channel, where the bot may not be allowed to post
+        let sent = session
+            .channel_id
+            .send_message(&ctx.http, serenity::CreateMessage::new().content(&message))
+            .await;
+        if let Err(e) = sent {
+            warn!(
+                "Cannot send the level up message in {}: {e}",
+                session.channel_id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_eligible_members() {
+    let (lounge, afk, solo) = (ChannelId::new(10), ChannelId::new(11), ChannelId::new(12));
+    let member = |user_id, channel_id| VoiceMember {
+        user_id: UserId::new(user_id),
+        channel_id,
+        is_bot: false,
+        silenced: false,
+    };
+    let voice_members = [
+        member(1, lounge),
+        VoiceMember {
+            silenced: true,
+            ..member(2, lounge)
+        },
+        VoiceMember {
+            is_bot: true,
+            ..member(3, solo)
+        },
+        member(4, solo),
+        member(5, afk),
+        member(6, afk),
+    ];
+
+    let eligible = eligible_members(&voice_members, Some(afk));
+    // The silenced member still keeps the other one company
+    assert_eq!(eligible, HashMap::from([(UserId::new(1), lounge)]));
+}
+
+#[test]
+fn test_update_sessions() {
+    let sessions = VoiceSessions::default();
+    let (guild_id, lounge) = (GuildId::new(1), ChannelId::new(10));
+    let (alice, bob) = (UserId::new(2), UserId::new(3));
+    let start = Instant::now();
+
+    // Bob joins Alice
+    let both = HashMap::from([(alice, lounge), (bob, lounge)]);
+    let ended = update_sessions(
+        &sessions,
+        guild_id,
+        bob,
+        &[None, Some(lounge)],
+        &both,
+        start,
+    );
+    assert!(ended.is_empty());
+    assert_eq!(sessions.lock().unwrap().len(), 2);
+
+    // Bob leaves, Alice is alone
+    let later = start + std::time::Duration::from_secs(300);
+    let ended = update_sessions(
+        &sessions,
+        guild_id,
+        bob,
+        &[Some(lounge), None],
+        &HashMap::new(),
+        later,
+    );
+    assert_eq!(ended.len(), 2);
+    assert!(sessions.lock().unwrap().is_empty());
+}
@@ -0,0 +1,85 @@
+use poise::serenity_prelude::{ChannelId, RoleId};
+use tracing::{debug, instrument};
+
+use super::queries;
+use crate::{
+    database::Db,
+    levels::models::{XpSettings, XpSettingsCache},
+    Error,
+};
+
+/// Xp settings of the guild, read from the database the first time only
+#[instrument(skip(db, cache))]
+pub async fn get_xp_settings(
+    db: &Db,
+    cache: &XpSettingsCache,
+    guild_id: u64,
+) -> Result<XpSettings, Error> {
+    if let Some(settings) = cache.lock().unwrap().get(&guild_id) {
+        return Ok(settings.clone());
+    }
+
+    let mut settings = queries::get_xp_settings(db, guild_id)
+        .await?
+        .unwrap_or_default();
+    settings.channel_multipliers = queries::get_channel_multipliers(db, guild_id)
+        .await?
+        .into_iter()
+        .map(|(channel_id, multiplier)| (ChannelId::new(channel_id), multiplier))
+        .collect();
+    settings.role_multipliers = queries::get_role_multipliers(db, guild_id)
+        .await?
+        .into_iter()
+        .map(|(role_id, multiplier)| (RoleId::new(role_id), multiplier))
+        .collect();
+    debug!("Xp settings of guild {guild_id}: {settings:?}");
+    cache.lock().unwrap().insert(guild_id, settings.clone());
+
+    Ok(settings)
+}
+
+/// Store the new xp settings of the guild, and replace the cached ones
+#[instrument(skip(db, cache))]
+pub async fn set_xp_settings(
+    db: &Db,
+    cache: &XpSettingsCache,
+    guild_id: u64,
+    settings: &XpSettings,
+) -> Result<(), Error> {
+    queries::set_xp_settings(db, guild_id, settings).await?;
+    cache.lock().unwrap().insert(guild_id, settings.clone());
+
+    Ok(())
+}
+
+/// Store the xp multiplier of a channel, `None` to remove it
+#[instrument(skip(db, cache))]
+pub async fn set_channel_multiplier(
+    db: &Db,
+    cache: &XpSettingsCache,
+    guild_id: u64,
+    channel_id: ChannelId,
+    multiplier: Option<f64>,
+) -> Result<(), Error> {
+    queries::set_channel_multiplier(db, guild_id, channel_id.get(), multiplier).await?;
+    // Read again on the next message
+    cache.lock().unwrap().remove(&guild_id);
+
+    Ok(())
+}
+
+/// Store the xp multiplier of a role, `None` to remove it
+#[instrument(skip(db, cache))]
+pub async fn set_role_multiplier(
+    db: &Db,
+    cache: &XpSettingsCache,
+    guild_id: u64,
+    role_id: RoleId,
+    multiplier: Option<f64>,
+) -> Result<(), Error> {
+    queries::set_role_multiplier(db, guild_id, role_id.get(), multiplier).await?;
+    // Read again on the next message
+    cache.lock().unwrap().remove(&guild_id);
+
+    Ok(())
+}
@@ -1,9 +1,14 @@
 use piet_common::Color;
-use poise::serenity_prelude::UserId;
+use poise::serenity_prelude::{ChannelId, GuildId, RoleId, UserId};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 use time::OffsetDateTime;
 
 use super::{
-    constants::{DELAY_ANTI_SPAM, MAX_XP_GAIN, MIN_XP_GAIN},
+    constants::{DELAY_ANTI_SPAM, MAX_XP_GAIN, MIN_XP_GAIN, VOICE_XP_PER_MINUTE},
     func::xp_func,
 };
 use crate::database::from_i64;
@@ -13,8 +18,9 @@ pub struct UserLevel {
     pub user_id: UserId,   // Discord user id
     pub xp: i64,           // User's xp
     pub level: i64,        // User's level
-    pub rank: i64,         // User's rank
+    pub rank: i64,         // User's rank, computed when read
     pub last_message: i64, // Timestamp of the last message posted
+    pub voice_xp: i64,     // Part of the xp earned in voice channels
 }
 
 impl UserLevel {
@@ -25,23 +31,34 @@ impl UserLevel {
             level: 0,
             rank: 0,
             last_message: 0,
+            voice_xp: 0,
         }
     }
 
-    pub fn gain_xp_if_not_spam(&mut self) -> bool {
+    pub fn gain_xp_if_not_spam(&mut self, settings: &XpSettings, multiplier: f64) -> bool {
         // Check the time between last and new message.
         // Return true if below anti_spam setting,
         // else false without adding xp
         let now: i64 = OffsetDateTime::now_utc().unix_timestamp();
-        if now - self.last_message > DELAY_ANTI_SPAM {
+        if now - self.last_message > settings.spam_delay {
             self.last_message = now;
-            self.xp += xp_func::rand_xp_points(MIN_XP_GAIN, MAX_XP_GAIN);
+            let points = xp_func::rand_xp_points(settings.min_xp_gain, settings.max_xp_gain);
+            self.xp += (points as f64 * multiplier).round() as i64;
             true
         } else {
             false
         }
     }
 
+    /// Add the xp of `minutes` spent in a voice channel, returns the xp gained
+    pub fn gain_voice_xp(&mut self, minutes: u64, settings: &XpSettings, multiplier: f64) -> i64 {
+        let points = minutes as f64 * settings.voice_xp_per_minute as f64 * multiplier;
+        let points = points.round() as i64;
+        self.xp += points;
+        self.voice_xp += points;
+        points
+    }
+
     pub fn has_level_up(&mut self) -> bool {
         let xp_to_next_level = xp_func::total_xp_required_for_level(self.level + 1);
         if self.xp >= xp_to_next_level {
@@ -63,6 +80,7 @@ impl From<(u64, i64, i64, i64, i64)> for UserLevel {
             level,
             rank,
             last_message,
+            voice_xp: 0,
         }
     }
 }
@@ -76,6 +94,7 @@ pub struct UserSql {
     pub level: i64,
     pub rank: i64,
     pub last_message: i64,
+    pub voice_xp: i64,
 }
 
 impl From<UserSql> for UserLevel {
@@ -86,6 +105,125 @@ impl From<UserSql> for UserLevel {
             level: value.level,
             rank: value.rank,
             last_message: value.last_message,
+            voice_xp: value.voice_xp,
+        }
+    }
+}
+
+/// Xp parameters of a guild, set with /levels config and /levels multipliers
+#[derive(Debug, Clone, PartialEq)]
+pub struct XpSettings {
+    pub min_xp_gain: i64,
+    pub max_xp_gain: i64,
+    /// Seconds between two messages earning xp
+    pub spam_delay: i64,
+    pub multiplier: f64,
+    /// Xp gained per minute in a voice channel, 0 for none
+    pub voice_xp_per_minute: i64,
+    /// Keep the lower reward roles on level up, instead of replacing them
+    pub stack_rewards: bool,
+    /// Multipliers of the messages in a channel, 0 for no xp
+    pub channel_multipliers: HashMap<ChannelId, f64>,
+    /// Multipliers of the messages of the members with a role, 0 for no xp
+    pub role_multipliers: HashMap<RoleId, f64>,
+}
+
+impl XpSettings {
+    /// Multiplier of the xp gained by a message in `channel_id` from a member with `roles`
+    ///
+    /// A channel or a role with a multiplier of 0 gives no xp, otherwise the best multiplier
+    /// of the roles applies.
+    pub fn multiplier_for(&self, channel_id: ChannelId, roles: &[RoleId]) -> f64 {
+        let channel = self
+            .channel_multipliers
+            .get(&channel_id)
+            .copied()
+            .unwrap_or(1.0);
+        let role_multipliers = roles
+            .iter()
+            .filter_map(|role_id| self.role_multipliers.get(role_id).copied())
+            .collect::<Vec<_>>();
+        let role = if role_multipliers.contains(&0.0) {
+            0.0
+        } else {
+            role_multipliers.into_iter().reduce(f64::max).unwrap_or(1.0)
+        };
+
+        self.multiplier * channel * role
+    }
+}
+
+impl Default for XpSettings {
+    fn default() -> Self {
+        Self {
+            min_xp_gain: MIN_XP_GAIN,
+            max_xp_gain: MAX_XP_GAIN,
+            spam_delay: DELAY_ANTI_SPAM,
+            multiplier: 1.0,
+            voice_xp_per_minute: VOICE_XP_PER_MINUTE,
+            stack_rewards: true,
+            channel_multipliers: HashMap::new(),
+            role_multipliers: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct XpSettingsSql {
+    pub min_xp_gain: i64,
+    pub max_xp_gain: i64,
+    pub spam_delay: i64,
+    pub xp_multiplier: f64,
+    pub stack_rewards: bool,
+    pub voice_xp_per_minute: i64,
+}
+
+impl From<XpSettingsSql> for XpSettings {
+    fn from(value: XpSettingsSql) -> Self {
+        Self {
+            min_xp_gain: value.min_xp_gain,
+            max_xp_gain: value.max_xp_gain,
+            spam_delay: value.spam_delay,
+            multiplier: value.xp_multiplier,
+            voice_xp_per_minute: value.voice_xp_per_minute,
+            stack_rewards: value.stack_rewards,
+            channel_multipliers: HashMap::new(),
+            role_multipliers: HashMap::new(),
+        }
+    }
+}
+
+/// Xp settings already read from the database, by guild_id
+pub type XpSettingsCache = Arc<Mutex<HashMap<u64, XpSettings>>>;
+
+/// Time since a member started earning xp in a voice channel
+#[derive(Debug, Clone, Copy)]
+pub struct VoiceSession {
+    pub channel_id: ChannelId,
+    pub since: Instant,
+}
+
+/// Voice sessions by guild and member
+pub type VoiceSessions = Arc<Mutex<HashMap<(GuildId, UserId), VoiceSession>>>;
+
+/// Role given to the members reaching `level`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelReward {
+    pub role_id: RoleId,
+    pub level: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LevelRewardSql {
+    pub role_id: i64,
+    pub level: i64,
+}
+
+impl From<LevelRewardSql> for LevelReward {
+    fn from(value: LevelRewardSql) -> Self {
+        Self {
+            role_id: RoleId::from(from_i64(value.role_id)),
+            level: value.level,
         }
     }
 }
@@ -123,3 +261,50 @@ impl UserInfoCard {
         )
     }
 }
+
+#[test]
+fn test_gain_xp_with_settings() {
+    let settings = XpSettings {
+        min_xp_gain: 20,
+        max_xp_gain: 20,
+        ..Default::default()
+    };
+    let mut user = UserLevel::new(1);
+
+    assert!(user.gain_xp_if_not_spam(&settings, 1.5));
+    assert_eq!(user.xp, 30);
+    // The next message is too soon
+    assert!(!user.gain_xp_if_not_spam(&settings, 1.5));
+    assert_eq!(user.xp, 30);
+}
+
+#[test]
+fn test_multiplier_for() {
+    let (events, spam) = (ChannelId::new(1), ChannelId::new(2));
+    let (booster, muted, regular) = (RoleId::new(3), RoleId::new(4), RoleId::new(5));
+    let settings = XpSettings {
+        multiplier: 2.0,
+        channel_multipliers: HashMap::from([(events, 2.0), (spam, 0.0)]),
+        role_multipliers: HashMap::from([(booster, 1.5), (muted, 0.0), (regular, 1.2)]),
+        ..Default::default()
+    };
+
+    assert_eq!(settings.multiplier_for(ChannelId::new(9), &[]), 2.0);
+    assert_eq!(settings.multiplier_for(events, &[]), 4.0);
+    assert_eq!(settings.multiplier_for(spam, &[booster]), 0.0);
+    assert_eq!(settings.multiplier_for(events, &[regular, booster]), 6.0);
+    assert_eq!(settings.multiplier_for(events, &[booster, muted]), 0.0);
+}
+
+#[test]
+fn test_gain_voice_xp() {
+    let settings = XpSettings {
+        voice_xp_per_minute: 3,
+        ..Default::default()
+    };
+    let mut user = UserLevel::new(1);
+    user.xp = 100;
+
+    assert_eq!(user.gain_voice_xp(10, &settings, 1.5), 45);
+    assert_eq!((user.xp, user.voice_xp), (145, 45));
+}
@@ -1,7 +1,7 @@
 use poise::serenity_prelude::UserId;
 use tracing::instrument;
 
-use super::models::{UserLevel, UserSql};
+use super::models::{LevelReward, LevelRewardSql, UserLevel, UserSql, XpSettings, XpSettingsSql};
 use crate::{
     database::{from_i64, to_i64, Db},
     Error,
@@ -10,34 +10,50 @@ use crate::{
 /// Return `UserLevel` corresponding to `user_id` in the database.
 ///
 /// If no user is found, create a new entry with `user_id` and returns
-/// new `UserLevel`.
+/// new `UserLevel`, ranked among the other users.
 #[instrument]
 pub async fn get_user(db: &Db, user_id: u64, guild_id: u64) -> Result<UserLevel, Error> {
     // Bit-cast `user_id` from u64 to i64, as SQLite does not support u64 integer
     let user_id = to_i64(user_id);
     let guild_id = to_i64(guild_id);
 
-    let response = sqlx::query_as!(
-        UserSql,
-        "SELECT * FROM levels WHERE user_id = ? AND guild_id = ?",
+    if let Some(record) = select_user(db, user_id, guild_id).await? {
+        return Ok(UserLevel::from(record));
+    }
+
+    sqlx::query!(
+        "INSERT INTO levels (user_id, guild_id) VALUES (?, ?)",
         user_id,
         guild_id
     )
+    .execute(&db.pool)
+    .await?;
+    let record = select_user(db, user_id, guild_id)
+        .await?
+        .ok_or("User not found after insert")?;
+    Ok(UserLevel::from(record))
+}
+
+/// Select the entry of `user_id`, with its rank in the guild
+async fn select_user(db: &Db, user_id: i64, guild_id: i64) -> Result<Option<UserSql>, Error> {
+    // The rank is computed among all the members of the guild, before keeping the user
+    let response = sqlx::query_as!(
+        UserSql,
+        r#"SELECT user_id AS "user_id!", guild_id AS "guild_id!", xp AS "xp!",
+                level AS "level!", rank AS "rank!: i64", last_message AS "last_message!",
+                voice_xp AS "voice_xp!"
+            FROM (
+                SELECT *, RANK() OVER (ORDER BY xp DESC) AS rank
+                    FROM levels WHERE guild_id = ?
+            )
+            WHERE user_id = ?"#,
+        guild_id,
+        user_id
+    )
     .fetch_optional(&db.pool)
     .await?;
 
-    if let Some(record) = response {
-        Ok(UserLevel::from(record))
-    } else {
-        sqlx::query!(
-            "INSERT INTO levels (user_id, guild_id) VALUES (?, ?)",
-            user_id,
-            guild_id
-        )
-        .execute(&db.pool)
-        .await?;
-        Ok(UserLevel::new(from_i64(user_id)))
-    }
+    Ok(response)
 }
 
 /// Update user's entry in the database with new values.
@@ -49,11 +65,12 @@ pub async fn update_user(db: &Db, user: &UserLevel, guild_id: u64) -> Result<(),
 
     sqlx::query!(
         "UPDATE levels
-            SET xp = ?, level = ?, last_message = ?
+            SET xp = ?, level = ?, last_message = ?, voice_xp = ?
             WHERE user_id = ? AND guild_id = ?",
         user.xp,
         user.level,
         user.last_message,
+        user.voice_xp,
         user_id,
         guild_id
     )
@@ -63,44 +80,54 @@ pub async fn update_user(db: &Db, user: &UserLevel, guild_id: u64) -> Result<(),
     Ok(())
 }
 
-/// Update user rank in the database
+/// Get all entries in the database and returns a `Vec<UserLevel>`, by rank
 #[instrument]
-pub async fn update_ranks(db: &Db, users: Vec<UserLevel>, guild_id: u64) -> Result<(), Error> {
+pub async fn get_all_users(db: &Db, guild_id: u64) -> Result<Vec<UserLevel>, Error> {
     let guild_id = to_i64(guild_id);
 
-    for user in users {
-        let user_id = to_i64(user.user_id.get());
+    let response = sqlx::query_as!(
+        UserSql,
+        r#"SELECT user_id, guild_id, xp, level, RANK() OVER (ORDER BY xp DESC) AS "rank!: i64",
+                last_message, voice_xp
+            FROM levels WHERE guild_id = ?
+            ORDER BY xp DESC"#,
+        guild_id
+    )
+    .fetch_all(&db.pool)
+    .await?;
 
-        sqlx::query!(
-            "UPDATE levels
-                SET rank = ?
-                WHERE user_id = ? AND guild_id = ?",
-            user.rank,
-            user_id,
-            guild_id
-        )
-        .execute(&db.pool)
-        .await?;
-    }
+    let all_users = response
+        .iter()
+        .map(|record| UserLevel::from(*record))
+        .collect();
 
-    Ok(())
+    Ok(all_users)
 }
 
-/// Get all entries in the database and returns a `Vec<UserLevel>`
+/// Get the `number` best ranked users of the guild
 #[instrument]
-pub async fn get_all_users(db: &Db, guild_id: u64) -> Result<Vec<UserLevel>, Error> {
+pub async fn get_top_users(db: &Db, guild_id: u64, number: i64) -> Result<Vec<UserLevel>, Error> {
     let guild_id = to_i64(guild_id);
 
-    let response = sqlx::query_as!(UserSql, "SELECT * FROM levels WHERE guild_id = ?", guild_id)
-        .fetch_all(&db.pool)
-        .await?;
+    let response = sqlx::query_as!(
+        UserSql,
+        r#"SELECT user_id, guild_id, xp, level, RANK() OVER (ORDER BY xp DESC) AS "rank!: i64",
+                last_message, voice_xp
+            FROM levels WHERE guild_id = ?
+            ORDER BY xp DESC
+            LIMIT ?"#,
+        guild_id,
+        number
+    )
+    .fetch_all(&db.pool)
+    .await?;
 
-    let all_users = response
+    let top_users = response
         .iter()
         .map(|record| UserLevel::from(*record))
         .collect();
 
-    Ok(all_users)
+    Ok(top_users)
 }
 
 /// Import levels from Mee6.
@@ -118,13 +145,12 @@ pub async fn import_from_mee6(db: &Db, users: Vec<UserLevel>, guild_id: u64) ->
     for user in users {
         let user_id = to_i64(user.user_id.get());
         sqlx::query!(
-            "INSERT INTO levels (user_id, guild_id, xp, level, rank, last_message)
-                VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO levels (user_id, guild_id, xp, level, last_message)
+                VALUES (?, ?, ?, ?, ?)",
             user_id,
             guild_id,
             user.xp,
             user.level,
-            user.rank,
             user.last_message,
         )
         .execute(&db.pool)
@@ -133,3 +159,208 @@ pub async fn import_from_mee6(db: &Db, users: Vec<UserLevel>, guild_id: u64) ->
 
     Ok(())
 }
+
+/// Get the xp settings of the guild, `None` if they were never set
+#[instrument]
+pub async fn get_xp_settings(db: &Db, guild_id: u64) -> Result<Option<XpSettings>, Error> {
+    let guild_id = to_i64(guild_id);
+
+    let response = sqlx::query_as!(
+        XpSettingsSql,
+        "SELECT min_xp_gain, max_xp_gain, spam_delay, xp_multiplier, stack_rewards,
+                voice_xp_per_minute
+            FROM guild_settings WHERE guild_id = ?",
+        guild_id
+    )
+    .fetch_optional(&db.pool)
+    .await?;
+
+    Ok(response.map(XpSettings::from))
+}
+
+/// Insert or replace the xp settings of the guild
+#[instrument]
+pub async fn set_xp_settings(db: &Db, guild_id: u64, settings: &XpSettings) -> Result<(), Error> {
+    let guild_id = to_i64(guild_id);
+
+    sqlx::query!(
+        "INSERT INTO guild_settings
+            (guild_id, min_xp_gain, max_xp_gain, spam_delay, xp_multiplier, stack_rewards,
+                voice_xp_per_minute)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(guild_id) DO UPDATE SET
+                min_xp_gain = excluded.min_xp_gain,
+                max_xp_gain = excluded.max_xp_gain,
+                spam_delay = excluded.spam_delay,
+                xp_multiplier = excluded.xp_multiplier,
+                stack_rewards = excluded.stack_rewards,
+                voice_xp_per_minute = excluded.voice_xp_per_minute",
+        guild_id,
+        settings.min_xp_gain,
+        settings.max_xp_gain,
+        settings.spam_delay,
+        settings.multiplier,
+        settings.stack_rewards,
+        settings.voice_xp_per_minute
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the reward roles of the guild, by ascending level
+#[instrument]
+pub async fn get_rewards(db: &Db, guild_id: u64) -> Result<Vec<LevelReward>, Error> {
+    let guild_id = to_i64(guild_id);
+
+    let response = sqlx::query_as!(
+        LevelRewardSql,
+        "SELECT role_id, level FROM level_rewards WHERE guild_id = ? ORDER BY level, role_id",
+        guild_id
+    )
+    .fetch_all(&db.pool)
+    .await?;
+
+    Ok(response.into_iter().map(LevelReward::from).collect())
+}
+
+/// Give `role_id` at `level`, replacing the level of the role if it was already a reward
+#[instrument]
+pub async fn set_reward(db: &Db, guild_id: u64, role_id: u64, level: i64) -> Result<(), Error> {
+    let guild_id = to_i64(guild_id);
+    let role_id = to_i64(role_id);
+
+    sqlx::query!(
+        "INSERT INTO level_rewards (guild_id, role_id, level) VALUES (?, ?, ?)
+            ON CONFLICT(guild_id, role_id) DO UPDATE SET level = excluded.level",
+        guild_id,
+        role_id,
+        level
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Remove `role_id` from the rewards, returns `false` if it was not one
+#[instrument]
+pub async fn delete_reward(db: &Db, guild_id: u64, role_id: u64) -> Result<bool, Error> {
+    let guild_id = to_i64(guild_id);
+    let role_id = to_i64(role_id);
+
+    let result = sqlx::query!(
+        "DELETE FROM level_rewards WHERE guild_id = ? AND role_id = ?",
+        guild_id,
+        role_id
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get the xp multipliers of the channels of the guild, as `(channel_id, multiplier)`
+#[instrument]
+pub async fn get_channel_multipliers(db: &Db, guild_id: u64) -> Result<Vec<(u64, f64)>, Error> {
+    let guild_id = to_i64(guild_id);
+
+    let response = sqlx::query!(
+        "SELECT channel_id, multiplier FROM xp_channels WHERE guild_id = ?",
+        guild_id
+    )
+    .fetch_all(&db.pool)
+    .await?;
+
+    Ok(response
+        .into_iter()
+        .map(|record| (from_i64(record.channel_id), record.multiplier))
+        .collect())
+}
+
+/// Set the xp multiplier of a channel, `None` removes it
+#[instrument]
+pub async fn set_channel_multiplier(
+    db: &Db,
+    guild_id: u64,
+    channel_id: u64,
+    multiplier: Option<f64>,
+) -> Result<(), Error> {
+    let guild_id = to_i64(guild_id);
+    let channel_id = to_i64(channel_id);
+
+    if let Some(multiplier) = multiplier {
+        sqlx::query!(
+            "INSERT INTO xp_channels (guild_id, channel_id, multiplier) VALUES (?, ?, ?)
+                ON CONFLICT(guild_id, channel_id) DO UPDATE SET multiplier = excluded.multiplier",
+            guild_id,
+            channel_id,
+            multiplier
+        )
+        .execute(&db.pool)
+        .await?;
+    } else {
+        sqlx::query!(
+            "DELETE FROM xp_channels WHERE guild_id = ? AND channel_id = ?",
+            guild_id,
+            channel_id
+        )
+        .execute(&db.pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Get the xp multipliers of the roles of the guild, as `(role_id, multiplier)`
+#[instrument]
+pub async fn get_role_multipliers(db: &Db, guild_id: u64) -> Result<Vec<(u64, f64)>, Error> {
+    let guild_id = to_i64(guild_id);
+
+    let response = sqlx::query!(
+        "SELECT role_id, multiplier FROM xp_roles WHERE guild_id = ?",
+        guild_id
+    )
+    .fetch_all(&db.pool)
+    .await?;
+
+    Ok(response
+        .into_iter()
+        .map(|record| (from_i64(record.role_id), record.multiplier))
+        .collect())
+}
+
+/// Set the xp multiplier of a role, `None` removes it
+#[instrument]
+pub async fn set_role_multiplier(
+    db: &Db,
+    guild_id: u64,
+    role_id: u64,
+    multiplier: Option<f64>,
+) -> Result<(), Error> {
+    let guild_id = to_i64(guild_id);
+    let role_id = to_i64(role_id);
+
+    if let Some(multiplier) = multiplier {
+        sqlx::query!(
+            "INSERT INTO xp_roles (guild_id, role_id, multiplier) VALUES (?, ?, ?)
+                ON CONFLICT(guild_id, role_id) DO UPDATE SET multiplier = excluded.multiplier",
+            guild_id,
+            role_id,
+            multiplier
+        )
+        .execute(&db.pool)
+        .await?;
+    } else {
+        sqlx::query!(
+            "DELETE FROM xp_roles WHERE guild_id = ? AND role_id = ?",
+            guild_id,
+            role_id
+        )
+        .execute(&db.pool)
+        .await?;
+    }
+
+    Ok(())
+}
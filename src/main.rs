@@ -49,6 +49,10 @@ pub struct Data {
     pub db: Arc<Db>,
     pub roulette_map: Arc<Mutex<HashMap<UserId, (u8, i64)>>>,
     pub hook_listener: Arc<HookListener>,
+    pub pending_subs: youtube::models::PendingSubs,
+    pub invidious: Arc<youtube::invidious::InvidiousClient>,
+    pub xp_settings: levels::models::XpSettingsCache,
+    pub voice_sessions: levels::models::VoiceSessions,
 }
 
 // ---------------------------------------- Main -----------------------------------------
@@ -79,11 +83,11 @@ async fn main() -> Result<(), Error> {
         | serenity::GatewayIntents::GUILD_MEMBERS
         | serenity::GatewayIntents::GUILD_PRESENCES
         | serenity::GatewayIntents::GUILD_MESSAGES
+        | serenity::GatewayIntents::GUILD_VOICE_STATES
         | serenity::GatewayIntents::DIRECT_MESSAGES
         | serenity::GatewayIntents::MESSAGE_CONTENT;
 
-    let cfg_file = std::fs::read_to_string("config.toml")?;
-    let config: Config = toml::from_str(&cfg_file)?;
+    let config = Config::load()?;
 
     //let db_url = env::var("DATABASE_URL")?;
     let db_url = &config.database;
@@ -93,11 +97,17 @@ async fn main() -> Result<(), Error> {
     db.run_migrations().await?;
 
     // Create webhook listener and get receiver
-    let hook_listener = HookListener::builder()
+    let hub_secrets = youtube::queries::get_hub_secrets(&db).await?;
+    let mut hook_listener = HookListener::builder()
         .listener(&config.brzthook.ip_addr, config.brzthook.port)?
         .callback(&config.brzthook.callback)
+        .routes(&config.brzthook.routes)?
         .new_only(config.brzthook.new_only)
-        .build()?;
+        .secrets(hub_secrets);
+    if let Some(hub) = &config.brzthook.hub {
+        hook_listener = hook_listener.hub(hub);
+    }
+    let hook_listener = hook_listener.build()?;
 
     let options = poise::FrameworkOptions {
         commands: vec![
@@ -106,6 +116,8 @@ async fn main() -> Result<(), Error> {
             admin::commands::import_mee6_levels(),
             admin::commands::set_xp(),
             admin::commands::shutdown(),
+            admin::commands::reload_listener(),
+            levels::commands::levels(),
             levels::commands::rank(),
             levels::commands::top(),
             mention_roles::commands::gimmeroles(),
@@ -161,6 +173,10 @@ async fn main() -> Result<(), Error> {
                     db: Arc::new(db),
                     roulette_map: Arc::new(Mutex::new(HashMap::new())),
                     hook_listener: Arc::new(hook_listener),
+                    pending_subs: Arc::new(Mutex::new(HashMap::new())),
+                    invidious: Arc::new(youtube::invidious::InvidiousClient::new()),
+                    xp_settings: Arc::new(Mutex::new(HashMap::new())),
+                    voice_sessions: Arc::new(Mutex::new(HashMap::new())),
                 })
             })
         })
@@ -1,3 +1,3 @@
 pub mod commands;
-mod queries;
+pub mod queries;
 mod util;
@@ -0,0 +1,70 @@
+use poise::{serenity_prelude::ChannelId, ChoiceParameter};
+use tracing::instrument;
+
+use super::{
+    func::{autocomplete_sublist, find_sub},
+    queries,
+};
+use crate::{youtube::models::VideoKind, Context, Error};
+
+/// Choose which kinds of video of a channel are announced
+///
+/// e.g. turn off Shorts, or announce only the live streams.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_WEBHOOKS",
+    ephemeral,
+    category = "Youtube"
+)]
+pub(super) async fn announce(
+    ctx: Context<'_>,
+    #[description = "Name of the channel"]
+    #[autocomplete = "autocomplete_sublist"]
+    name: String,
+    #[description = "Kind of video"] kind: VideoKind,
+    #[description = "Announce the videos of this kind"] enabled: bool,
+    #[description = "Discord channel the videos are posted in"] channel: Option<ChannelId>,
+) -> Result<(), Error> {
+    let db = &ctx.data().db;
+    let Some(mut sub) = find_sub(&ctx, &name, channel).await? else {
+        return Ok(());
+    };
+
+    queries::update_announce(
+        db,
+        kind,
+        enabled,
+        &sub.yt_channel_id,
+        sub.guild_id,
+        sub.post_channel_id,
+    )
+    .await?;
+    match kind {
+        VideoKind::Upload => sub.announce_uploads = enabled,
+        VideoKind::Short => sub.announce_shorts = enabled,
+        VideoKind::Premiere => sub.announce_premieres = enabled,
+        VideoKind::Live => sub.announce_lives = enabled,
+    }
+
+    let announced = [
+        VideoKind::Upload,
+        VideoKind::Short,
+        VideoKind::Premiere,
+        VideoKind::Live,
+    ]
+    .into_iter()
+    .filter(|kind| sub.announces(*kind))
+    .map(|kind| kind.name())
+    .collect::<Vec<_>>();
+    let name = &sub.yt_channel_name;
+    let content = if announced.is_empty() {
+        format!("Nothing from {name} is announced anymore")
+    } else {
+        format!("Announced from {name}: {}", announced.join(", "))
+    };
+
+    ctx.say(content).await?;
+    Ok(())
+}
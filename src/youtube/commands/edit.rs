@@ -0,0 +1,92 @@
+use poise::{serenity_prelude::ChannelId, ChoiceParameter};
+use time::OffsetDateTime;
+use tracing::instrument;
+
+use super::{
+    constants::YOUTUBE_VIDEO_PREFIX,
+    func::{autocomplete_sublist, find_sub},
+    queries,
+};
+use crate::{
+    youtube::{
+        models::VideoKind,
+        template::{self, Announcement},
+    },
+    Context, Error,
+};
+
+/// Change the announcement message of a subscription
+///
+/// Placeholders: {channel}, {title}, {url}, {published} and {role}; \n for a new line.
+/// Leave the template empty to go back to the default message.
+///
+/// Each kind of video has its own message; the message of the uploads is used for
+/// the kinds without one.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_WEBHOOKS",
+    ephemeral,
+    category = "Youtube"
+)]
+pub(super) async fn edit(
+    ctx: Context<'_>,
+    #[description = "Name of the channel"]
+    #[autocomplete = "autocomplete_sublist"]
+    name: String,
+    #[description = "Announcement message, e.g. \"{role} New video from {channel}: {url}\""]
+    template: Option<String>,
+    #[description = "Kind of video, uploads by default"] kind: Option<VideoKind>,
+    #[description = "Discord channel the videos are posted in"] channel: Option<ChannelId>,
+) -> Result<(), Error> {
+    let kind = kind.unwrap_or(VideoKind::Upload);
+    let db = &ctx.data().db;
+    let Some(sub) = find_sub(&ctx, &name, channel).await? else {
+        return Ok(());
+    };
+
+    let template = match template.as_deref().map(template::parse).transpose() {
+        Ok(template) => template,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+    queries::update_template(
+        db,
+        kind,
+        template.as_deref(),
+        &sub.yt_channel_id,
+        sub.guild_id,
+        sub.post_channel_id,
+    )
+    .await?;
+    let template = match template {
+        Some(template) => template,
+        None if kind == VideoKind::Upload => kind.default_template().to_string(),
+        None => sub
+            .template(VideoKind::Upload)
+            .unwrap_or(kind.default_template())
+            .to_string(),
+    };
+
+    let url = format!("{YOUTUBE_VIDEO_PREFIX}dQw4w9WgXcQ");
+    let preview = template::render(
+        Some(&template),
+        &Announcement {
+            channel: &sub.yt_channel_name,
+            title: "Video title",
+            url: &url,
+            published: OffsetDateTime::now_utc(),
+            role: None,
+        },
+    );
+    ctx.say(format!(
+        "Announcement of {} for {} updated:\n>>> {preview}",
+        sub.yt_channel_name,
+        kind.name()
+    ))
+    .await?;
+    Ok(())
+}
@@ -1,19 +1,58 @@
+use poise::{
+    serenity_prelude::{self as serenity, ChannelId, Mentionable},
+    CreateReply,
+};
+
 use super::queries;
 use crate::{Context, Error};
 
+/// Discord limit of fields in an embed
+const MAX_FIELDS: usize = 25;
+
 /// List all subs in the guild
 #[poise::command(slash_command, guild_only, category = "Youtube")]
 pub(super) async fn list(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().ok_or("Not in guild")?;
-    let subs: Vec<String> = queries::get_subs_list(&ctx.data().db)
-        .await?
-        .into_iter()
-        .filter(|s| s.guild_id == guild_id.get())
-        .map(|s| s.yt_channel_name)
-        .collect();
-
-    let content = format!("**List of subscribed channels:**\n>>> {}", subs.join("\n"));
-    ctx.say(&content).await?;
+    let subs = queries::get_guild_subs(&ctx.data().db, guild_id.get()).await?;
+    if subs.is_empty() {
+        ctx.say("No subscribed channel").await?;
+        return Ok(());
+    }
+
+    // Subs are ordered by channel, one field for each with all its targets
+    let mut fields: Vec<(String, String)> = vec![];
+    for (i, sub) in subs.iter().enumerate() {
+        let target = format!("→ {}", ChannelId::new(sub.post_channel_id).mention());
+        if i > 0 && subs[i - 1].yt_channel_id == sub.yt_channel_id {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push('\n');
+                value.push_str(&target);
+            }
+            continue;
+        }
+
+        let status = match &sub.denied_reason {
+            Some(reason) => format!("Denied by the hub: {reason}"),
+            None => format!("Expires <t:{}:R>", sub.expire_on.unix_timestamp()),
+        };
+        fields.push((sub.yt_channel_name.clone(), format!("{status}\n{target}")));
+    }
+
+    let mut embed = serenity::CreateEmbed::new().title("Subscribed channels");
+    if fields.len() > MAX_FIELDS {
+        embed = embed.footer(serenity::CreateEmbedFooter::new(format!(
+            "and {} more",
+            fields.len() - MAX_FIELDS
+        )));
+    }
+    let embed = embed.fields(
+        fields
+            .into_iter()
+            .take(MAX_FIELDS)
+            .map(|(name, value)| (name, value, false)),
+    );
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
 
     Ok(())
 }
@@ -1,5 +1,9 @@
+mod announce;
+mod edit;
 mod list;
+mod move_sub;
 mod search;
+mod status;
 mod sub;
 mod sub_details;
 mod unsub;
@@ -7,19 +11,33 @@ mod unsub;
 use super::{constants, func, queries};
 use crate::{youtube, Context, Data, Error};
 
+use announce::announce;
+use edit::edit;
 use list::list;
+use move_sub::move_sub;
 use search::search;
+use status::status;
 use sub::sub;
 use sub_details::sub_details;
 use unsub::unsub;
 
 /// Commands for interacting with Youtube
 ///
-/// Subcommands: `search`, `sub`, `unsub`, `list`
+/// Subcommands: `search`, `sub`, `unsub`, `edit`, `announce`, `move`, `list`, `status`
 #[poise::command(
     slash_command,
     guild_only,
-    subcommands("search", "sub", "unsub", "list", "sub_details"),
+    subcommands(
+        "search",
+        "sub",
+        "unsub",
+        "edit",
+        "announce",
+        "move_sub",
+        "list",
+        "status",
+        "sub_details"
+    ),
     subcommand_required,
     category = "Youtube"
 )]
@@ -0,0 +1,61 @@
+use poise::serenity_prelude::{ChannelId, Mentionable};
+use tracing::instrument;
+
+use super::{
+    func::{autocomplete_sublist, find_sub},
+    queries,
+};
+use crate::{Context, Error};
+
+/// Post the videos of a subscription in another channel
+///
+/// The settings of the subscription are kept.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "move",
+    required_permissions = "MANAGE_WEBHOOKS",
+    ephemeral,
+    category = "Youtube"
+)]
+pub(super) async fn move_sub(
+    ctx: Context<'_>,
+    #[description = "Name of the channel"]
+    #[autocomplete = "autocomplete_sublist"]
+    name: String,
+    #[description = "Discord channel to post the videos in"]
+    #[channel_types("Text", "News")]
+    to: ChannelId,
+    #[description = "Discord channel the videos are posted in"] from: Option<ChannelId>,
+) -> Result<(), Error> {
+    let Some(sub) = find_sub(&ctx, &name, from).await? else {
+        return Ok(());
+    };
+
+    let moved = queries::move_sub(
+        &ctx.data().db,
+        &sub.yt_channel_id,
+        sub.guild_id,
+        sub.post_channel_id,
+        to.get(),
+    )
+    .await?;
+    let content = if moved {
+        format!(
+            "{} moved from {} to {}",
+            sub.yt_channel_name,
+            ChannelId::new(sub.post_channel_id).mention(),
+            to.mention()
+        )
+    } else {
+        format!(
+            "{} is already posted in {}",
+            sub.yt_channel_name,
+            to.mention()
+        )
+    };
+
+    ctx.say(content).await?;
+    Ok(())
+}
@@ -1,60 +1,330 @@
-use serde_json::Value;
-use tracing::{info, instrument, warn};
+use poise::{serenity_prelude as serenity, CreateReply};
+use std::time::Duration;
+use tracing::{debug, error, instrument, warn};
 
-use super::{constants::YOUTUBE_VIDEO_PREFIX, func::get_invidious_instances};
-use crate::{Context, Error};
+use super::constants::{YOUTUBE_CHANNEL_PREFIX, YOUTUBE_PLAYLIST_PREFIX, YOUTUBE_VIDEO_PREFIX};
+use crate::{
+    youtube::{
+        embed::YOUTUBE_RED,
+        invidious::{SearchDuration, SearchItem, SearchKind},
+    },
+    Context, Error,
+};
 
-/// Search a Youtube video.
+/// Results shown on a page of the embed
+const PAGE_SIZE: usize = 5;
+const INTERACTION_TIMEOUT: Duration = Duration::from_secs(60 * 2);
+
+const PREVIOUS_BUTTON: &str = "search_previous";
+const NEXT_BUTTON: &str = "search_next";
+const RESULTS_MENU: &str = "search_results";
+
+/// Search Youtube videos, channels or playlists.
 ///
-/// The bot will post the first video returned by the search phrase you entered.
+/// The results are listed in pages; the one picked in the menu is posted.
 ///
-/// It requests the Invidious API to get the video Id to avoid the need of a Google API Key.
+/// It requests the Invidious API to avoid the need of a Google API Key.
 /// The link posted is Youtube though.
 #[instrument(skip(ctx))]
-#[poise::command(prefix_command, slash_command, category = "Youtube")]
+#[poise::command(slash_command, category = "Youtube")]
 pub async fn search(
     ctx: Context<'_>,
-    #[rest]
-    #[description = "search input"]
-    search: String,
+    #[description = "search input"] search: String,
+    #[description = "Type of the results, videos by default"] kind: Option<SearchKind>,
+    #[description = "Length of the videos"] duration: Option<SearchDuration>,
 ) -> Result<(), Error> {
     ctx.defer().await?;
 
-    // Request available invidious instance
-    let Some(instances) = get_invidious_instances().await? else {
-        warn!("No invidious instance found");
-        ctx.say("No Invidious instance found.").await?;
+    let kind = kind.unwrap_or(SearchKind::Video);
+    let results = match ctx.data().invidious.search(&search, kind, duration).await {
+        Ok(items) => items
+            .into_iter()
+            .filter_map(SearchResult::from_item)
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            warn!("Search failed: {e}");
+            vec![]
+        }
+    };
+    if results.is_empty() {
+        ctx.say("Nothing to see here.").await?;
         return Ok(());
+    }
+    debug!("{} results", results.len());
+
+    let last_page = (results.len() - 1) / PAGE_SIZE;
+    let mut page = 0;
+
+    let handle = ctx
+        .send(
+            CreateReply::default()
+                .embed(page_embed(&search, &results, page, last_page))
+                .components(page_components(&results, page, last_page)),
+        )
+        .await?;
+    let m = handle.message().await?;
+
+    // Browse the pages until a result is picked
+    let (interaction, result) = loop {
+        let Some(interaction) = m
+            .await_component_interaction(&ctx.serenity_context().shard)
+            .author_id(ctx.author().id)
+            .timeout(INTERACTION_TIMEOUT)
+            .await
+        else {
+            // Remove dangling components
+            handle
+                .edit(
+                    ctx,
+                    CreateReply::default()
+                        .embed(page_embed(&search, &results, page, last_page))
+                        .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        match &interaction.data.kind {
+            serenity::ComponentInteractionDataKind::Button => {
+                match interaction.data.custom_id.as_str() {
+                    PREVIOUS_BUTTON => page = page.saturating_sub(1),
+                    NEXT_BUTTON => page = (page + 1).min(last_page),
+                    _ => {}
+                }
+                interaction
+                    .create_response(
+                        ctx,
+                        serenity::CreateInteractionResponse::UpdateMessage(
+                            serenity::CreateInteractionResponseMessage::new()
+                                .embed(page_embed(&search, &results, page, last_page))
+                                .components(page_components(&results, page, last_page)),
+                        ),
+                    )
+                    .await?;
+            }
+            serenity::ComponentInteractionDataKind::StringSelect { values } => {
+                let Some(result) = values
+                    .first()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .and_then(|index| results.get(index))
+                else {
+                    error!("Invalid selected value: {values:?}");
+                    continue;
+                };
+                break (interaction.clone(), result);
+            }
+            _ => {
+                error!("Invalid ComponentInteractionDataKind");
+            }
+        }
     };
 
-    for instance in instances {
-        let Some(instance_uri) = &instance[1]["uri"].as_str() else {
-            continue;
+    // Replace the results with the link, so Discord shows its own preview
+    interaction
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content(&result.url)
+                    .embeds(vec![])
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// A search result, as listed in the embed
+#[derive(Debug)]
+struct SearchResult {
+    title: String,
+    url: String,
+    /// Channel, duration and view count of a video; counts of a channel or a playlist
+    details: String,
+}
+
+impl SearchResult {
+    fn from_item(item: SearchItem) -> Option<Self> {
+        let result = match item {
+            SearchItem::Video(video) => {
+                let duration = if video.length_seconds == 0 {
+                    "live".to_string()
+                } else {
+                    format_duration(video.length_seconds)
+                };
+                Self {
+                    title: video.title,
+                    url: format!("{YOUTUBE_VIDEO_PREFIX}{}", video.video_id),
+                    details: format!(
+                        "{} · {duration} · {} views",
+                        video.author,
+                        format_count(video.view_count)
+                    ),
+                }
+            }
+            SearchItem::Channel(channel) => Self {
+                title: channel.author,
+                url: format!("{YOUTUBE_CHANNEL_PREFIX}{}", channel.author_id),
+                details: format!(
+                    "{} subscribers · {} videos",
+                    format_count(channel.sub_count),
+                    channel.video_count
+                ),
+            },
+            SearchItem::Playlist(playlist) => Self {
+                title: playlist.title,
+                url: format!("{YOUTUBE_PLAYLIST_PREFIX}{}", playlist.playlist_id),
+                details: format!("{} · {} videos", playlist.author, playlist.video_count),
+            },
+            SearchItem::Other => return None,
         };
-        let query_url = format!("{instance_uri}/api/v1/search?q={search}&type=video");
+        Some(result)
+    }
+}
+
+fn page_embed(
+    search: &str,
+    results: &[SearchResult],
+    page: usize,
+    last_page: usize,
+) -> serenity::CreateEmbed {
+    let description = results
+        .iter()
+        .enumerate()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .map(|(index, result)| {
+            format!(
+                "**{}. [{}]({})**\n{}",
+                index + 1,
+                escape_link_text(&result.title),
+                result.url,
+                result.details
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
 
-        // Send GET request to the invidious instance
-        info!("GET {query_url}");
-        let response = reqwest::get(query_url).await?;
-        info!("Status: {}", response.status());
+    serenity::CreateEmbed::new()
+        .title(truncate(&format!("Results for \"{search}\""), 256))
+        .description(description)
+        .colour(YOUTUBE_RED)
+        .footer(serenity::CreateEmbedFooter::new(format!(
+            "Page {}/{}",
+            page + 1,
+            last_page + 1
+        )))
+}
 
-        // Process the response if response status code is Ok then exit loop
-        // If status code is not Ok, try with next instance
-        if response.status().is_success() {
-            let json: Value = response.json().await?;
-            let Some(video_id) = &json[0]["videoId"].as_str() else {
-                continue;
-            };
-            info!("Found video id: {video_id}");
+/// Menu to pick one of the results of the page, and buttons to change page
+fn page_components(
+    results: &[SearchResult],
+    page: usize,
+    last_page: usize,
+) -> Vec<serenity::CreateActionRow> {
+    let options = results
+        .iter()
+        .enumerate()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .map(|(index, result)| {
+            serenity::CreateSelectMenuOption::new(
+                truncate(&format!("{}. {}", index + 1, result.title), 100),
+                index.to_string(),
+            )
+            .description(truncate(&result.details, 100))
+        })
+        .collect();
+    let select_menu = serenity::CreateSelectMenu::new(
+        RESULTS_MENU,
+        serenity::CreateSelectMenuKind::String { options },
+    )
+    .placeholder("Post a result");
 
-            let url = format!("{YOUTUBE_VIDEO_PREFIX}{video_id}");
-            ctx.say(url).await?;
+    let buttons = vec![
+        serenity::CreateButton::new(PREVIOUS_BUTTON)
+            .label("Previous")
+            .style(serenity::ButtonStyle::Secondary)
+            .disabled(page == 0),
+        serenity::CreateButton::new(NEXT_BUTTON)
+            .label("Next")
+            .style(serenity::ButtonStyle::Secondary)
+            .disabled(page >= last_page),
+    ];
 
-            return Ok(());
+    vec![
+        serenity::CreateActionRow::SelectMenu(select_menu),
+        serenity::CreateActionRow::Buttons(buttons),
+    ]
+}
+
+/// `h:mm:ss`, or `m:ss` under an hour
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+/// Round large counts, e.g. 1.2K or 3.4M
+fn format_count(count: u64) -> String {
+    if count < 1_000 {
+        return count.to_string();
+    }
+    let mut value = count as f64;
+    for unit in ["K", "M"] {
+        value /= 1e3;
+        // Compare the rounded value, 999_950 is 1.0M and not 1000.0K
+        if (value * 10.0).round() < 10_000.0 {
+            return format!("{value:.1}{unit}");
         }
     }
+    format!("{:.1}B", value / 1e3)
+}
 
-    // If no request to any invidious instance returned with Ok
-    ctx.say("Nothing to see here.").await?;
-    Ok(())
+/// Cut `text` to `max` characters, the limit of some Discord fields
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated = text.chars().take(max - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// Keep brackets in a title from breaking the markdown link
+fn escape_link_text(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(42), "0:42");
+        assert_eq!(format_duration(754), "12:34");
+        assert_eq!(format_duration(3725), "1:02:05");
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1_234), "1.2K");
+        assert_eq!(format_count(3_400_000), "3.4M");
+        assert_eq!(format_count(2_000_000_000), "2.0B");
+        assert_eq!(format_count(999_949), "999.9K");
+        assert_eq!(format_count(999_950), "1.0M");
+        assert_eq!(format_count(999_999_999), "1.0B");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("ééééé", 3), "éé…");
+    }
 }
@@ -0,0 +1,77 @@
+use poise::{serenity_prelude as serenity, CreateReply};
+use time::{Duration, OffsetDateTime};
+
+use super::queries;
+use crate::{Context, Error};
+
+/// Discord limit of fields in an embed
+const MAX_FIELDS: usize = 25;
+
+/// Show the state of the subscriptions of the guild
+///
+/// Last verification by the hub, last notification received, next renewal and errors, or
+/// the last fetch of the feed in poll mode.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_WEBHOOKS",
+    ephemeral,
+    category = "Youtube"
+)]
+pub(super) async fn status(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
+    let yt_channels = queries::get_guild_yt_channels(&ctx.data().db, guild_id.get()).await?;
+    if yt_channels.is_empty() {
+        ctx.say("No subscribed channel").await?;
+        return Ok(());
+    }
+
+    let now = OffsetDateTime::now_utc();
+    let stale_after = Duration::days(ctx.data().config.brzthook.stale_days);
+    let mode = ctx.data().config.brzthook.mode;
+    let timestamp = |datetime: Option<OffsetDateTime>| {
+        datetime.map_or_else(
+            || "never".to_string(),
+            |datetime| format!("<t:{}:R>", datetime.unix_timestamp()),
+        )
+    };
+
+    let mut embed = serenity::CreateEmbed::new().title("Subscriptions status");
+    for yt_channel in yt_channels.iter().take(MAX_FIELDS) {
+        let problems = yt_channel.problems(now, stale_after, mode);
+        let name = if problems.is_empty() {
+            format!("✅ {}", yt_channel.yt_channel_name)
+        } else {
+            format!("⚠️ {}", yt_channel.yt_channel_name)
+        };
+        let mut lines = vec![];
+        if mode.push() {
+            lines.push(format!("Verified: {}", timestamp(yt_channel.verified_on)));
+            lines.push(format!(
+                "Last notification: {}",
+                timestamp(yt_channel.notified_on)
+            ));
+            lines.push(format!(
+                "Next renewal: {}",
+                timestamp(Some(yt_channel.renew_on()))
+            ));
+        }
+        if mode.poll() {
+            lines.push(format!("Last poll: {}", timestamp(yt_channel.polled_on)));
+        }
+        let mut value = lines.join("\n");
+        for problem in problems {
+            value.push_str(&format!("\n**{problem}**"));
+        }
+        embed = embed.field(name, value, false);
+    }
+    if yt_channels.len() > MAX_FIELDS {
+        embed = embed.footer(serenity::CreateEmbedFooter::new(format!(
+            "and {} more",
+            yt_channels.len() - MAX_FIELDS
+        )));
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
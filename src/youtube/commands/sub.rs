@@ -1,17 +1,36 @@
 use brzthook::Mode;
+use poise::serenity_prelude as serenity;
 use time::Duration;
-use tracing::instrument;
+use tokio::sync::oneshot;
+use tracing::{instrument, warn};
 
 use crate::{
-    youtube::{constants::EXPIRATION_DAYS, func::get_name_id, models::SubYtChannel, queries},
+    mention_roles,
+    youtube::{
+        constants::EXPIRATION_DAYS,
+        func::{confirm_channel, resolve_channel},
+        invidious::Channel,
+        models::{SubOutcome, SubYtChannel},
+        queries, template,
+    },
     Context, Error,
 };
 
+/// How long to wait for the hub to verify the subscription
+const VERIFICATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
 /// Create a new Youtube webhook
 ///
-/// The new videos will be posted in the channel where this command is called from
+/// The new videos will be posted in the channel where this command is called from,
+/// or in the channel given; a Youtube channel can be posted in several channels
+///
+/// url argument takes any address of the Youtube channel, e.g. https://www.youtube.com/@{name},
+/// or of one of its videos; the channel found is shown to confirm the subscription
+///
+/// template is the announcement message, with the placeholders {channel}, {title}, {url},
+/// {published} and {role}; \n for a new line
 ///
-/// name argument takes the address https://www.youtube.com/{id} or https://www.youtube.com/@{name}
+/// role is pinged on every announcement; it must be one of the roles of /roles
 #[instrument(skip(ctx))]
 #[poise::command(
     slash_command,
@@ -22,35 +41,121 @@ use crate::{
 )]
 pub(super) async fn sub(
     ctx: Context<'_>,
-    #[description = "Url of the Youtube channel"] url: String,
+    #[description = "Url of the Youtube channel or of one of its videos"] url: String,
+    #[description = "Announcement message, e.g. \"{role} New video from {channel}: {url}\""]
+    template: Option<String>,
+    #[description = "Mention role to ping on new videos"] role: Option<serenity::Role>,
+    #[description = "Discord channel to post the videos in, this one by default"]
+    #[channel_types("Text", "News")]
+    channel: Option<serenity::ChannelId>,
 ) -> Result<(), Error> {
     ctx.defer_ephemeral().await?;
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
 
-    let Some((author_name, author_id)) = get_name_id(&ctx, &url).await? else {
-        ctx.say("No channel found").await?;
-        return Ok(());
+    let template = match template.as_deref().map(template::parse).transpose() {
+        Ok(template) => template,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
     };
 
-    // Send the subscription request to the hub
-    ctx.data()
-        .hook_listener
-        .subscribe(&author_id, Mode::Subscribe)?;
+    // Members opt into mention roles, so only these may be pinged
+    if let Some(role) = &role {
+        let mention_role_ids =
+            mention_roles::queries::get_role_ids(&ctx.data().db, guild_id.get()).await?;
+        if !mention_role_ids.contains(&role.id.get()) {
+            ctx.say(format!("{} is not a mention role (see /roles)", role.name))
+                .await?;
+            return Ok(());
+        }
+    }
 
-    let content = format!("Subbed to {author_name}");
+    let Some(yt_channel) = resolve_channel(&ctx, &url).await? else {
+        return Ok(());
+    };
+    if !confirm_channel(&ctx, &yt_channel).await? {
+        return Ok(());
+    }
+    let Channel {
+        author: author_name,
+        author_id,
+        ..
+    } = yt_channel;
+
+    // Until the hub grants a lease
     let expire_on = time::OffsetDateTime::now_utc()
         .checked_add(Duration::days(EXPIRATION_DAYS))
         .ok_or("Webhook subscription: cannot set expiration date")?;
-
-    // Store in the database
-    let sub = SubYtChannel {
-        yt_channel_id: author_id,
-        yt_channel_name: author_name,
-        guild_id: ctx.guild_id().unwrap().get(),
-        post_channel_id: ctx.channel_id().get(),
+    let new_sub = |hub_secret| SubYtChannel {
+        yt_channel_id: author_id.clone(),
+        yt_channel_name: author_name.clone(),
+        guild_id: guild_id.get(),
+        post_channel_id: channel.unwrap_or_else(|| ctx.channel_id()).get(),
         expire_on,
+        hub_secret,
+        denied_reason: None,
+        template,
+        mention_role_id: role.map(|role| role.id.get()),
+        // Every kind is announced until changed with /yt announce
+        announce_uploads: true,
+        announce_shorts: true,
+        announce_premieres: true,
+        announce_lives: true,
+        template_short: None,
+        template_premiere: None,
+        template_live: None,
     };
     let db = &ctx.data().db;
-    queries::insert_sub(db, sub).await?;
+
+    // The hub cannot reach the bot to verify the subscription, the feed is polled instead
+    if !ctx.data().config.brzthook.mode.push() {
+        queries::insert_sub(db, new_sub(None)).await?;
+        ctx.say(format!("Subbed to {author_name}")).await?;
+        return Ok(());
+    }
+
+    // Wait for the verification of intent before sending the request,
+    // the hub may verify it before answering
+    let (tx, rx) = oneshot::channel();
+    let pending_subs = &ctx.data().pending_subs;
+    pending_subs
+        .lock()
+        .unwrap()
+        .entry(author_id.clone())
+        .or_default()
+        .push(tx);
+
+    // Send the subscription request to the hub
+    let hook_listener = &ctx.data().hook_listener;
+    if let Err(e) = hook_listener.subscribe(&author_id, Mode::Subscribe).await {
+        pending_subs.lock().unwrap().remove(&author_id);
+        return Err(e.into());
+    }
+    let hub_secret = hook_listener.secret(&author_id);
+
+    // Store in the database
+    queries::insert_sub(db, new_sub(hub_secret.clone())).await?;
+    // The hub keeps one secret per topic, shared by every guild subscribed to the channel
+    if let Some(hub_secret) = hub_secret {
+        queries::update_hub_secret(db, &hub_secret, &author_id).await?;
+    }
+
+    let content = match tokio::time::timeout(VERIFICATION_TIMEOUT, rx).await {
+        Ok(Ok(SubOutcome::Verified { expire_on })) => {
+            // The verification may have come before the subscription was stored
+            queries::update_expire_on(db, expire_on, &author_id).await?;
+            format!("Subbed to {author_name}")
+        }
+        Ok(Ok(SubOutcome::Denied { reason })) => {
+            queries::set_denied(db, &reason, &author_id).await?;
+            format!("Subscription to {author_name} denied by the hub: {reason}")
+        }
+        Ok(Err(_)) | Err(_) => {
+            warn!("No verification of intent received for {author_id}");
+            format!("Subscription to {author_name} sent, but not verified by the hub yet")
+        }
+    };
 
     ctx.say(&content).await?;
     Ok(())
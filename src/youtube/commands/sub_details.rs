@@ -1,7 +1,6 @@
-use piet_common::TextStorage;
 use poise::serenity_prelude::{CacheHttp, ChannelId, Mentionable};
 
-use super::{func::autocomplete_sublist, queries};
+use super::func::{autocomplete_sublist, find_sub};
 use crate::{Context, Error};
 
 #[poise::command(
@@ -14,18 +13,20 @@ use crate::{Context, Error};
 pub(super) async fn sub_details(
     ctx: Context<'_>,
     #[autocomplete = "autocomplete_sublist"] name: String,
+    channel: Option<ChannelId>,
 ) -> Result<(), Error> {
-    let db = &ctx.data().db;
-    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
-    let sub = queries::get_sub(db, &name, guild_id.get())
-        .await?
-        .ok_or("Youtube Id not found")?;
-    let callback = ctx.data().config.brzthook.callback.as_str();
-    let topic = format!(
-        "https://www.youtube.com/xml/feeds/videos.xml?channel_id={}",
-        &sub.yt_channel_id
-    );
-    let pshb_link = format!("https://pubsubhubbub.appspot.com/subscription-details?hub.callback={callback}&hub.topic={topic}&hub.secret=");
+    let Some(sub) = find_sub(&ctx, &name, channel).await? else {
+        return Ok(());
+    };
+    // The hub knows the subscription by its own callback route
+    let listener = &ctx.data().hook_listener;
+    let params = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("hub.callback", &listener.callback_url(&sub.yt_channel_id))
+        .append_pair("hub.topic", &listener.topic_url(&sub.yt_channel_id))
+        .append_pair("hub.secret", "")
+        .finish();
+    let hub = listener.hub.trim_end_matches('/');
+    let pshb_link = format!("{hub}/subscription-details?{params}");
     let post_chan = ChannelId::new(sub.post_channel_id);
 
     let content = format!(
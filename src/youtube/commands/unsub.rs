@@ -1,6 +1,7 @@
 use brzthook::Mode;
+use poise::serenity_prelude::ChannelId;
 
-use super::func::autocomplete_sublist;
+use super::func::{autocomplete_sublist, find_sub};
 use crate::{youtube::queries, Context, Error};
 
 /// Unsub and delete a webhook
@@ -19,25 +20,27 @@ pub(super) async fn unsub(
     #[description = "Name of the channel"]
     #[autocomplete = "autocomplete_sublist"]
     name: String,
+    #[description = "Discord channel the videos are posted in"] channel: Option<ChannelId>,
 ) -> Result<(), Error> {
     ctx.defer_ephemeral().await?;
 
     let db = &ctx.data().db;
-    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
-    let Some(sub) = queries::get_sub(db, &name, guild_id.get()).await? else {
-        ctx.say("No channel found with this name.").await?;
+    let Some(sub) = find_sub(&ctx, &name, channel).await? else {
         return Ok(());
     };
 
     let author_id = sub.yt_channel_id;
+    let left = queries::delete_sub(db, &author_id, sub.guild_id, sub.post_channel_id).await?;
 
-    ctx.data()
-        .hook_listener
-        .subscribe(&author_id, Mode::Unsubscribe)?;
+    // Other guilds, or channels, still need the notifications of the hub
+    if left == 0 && ctx.data().config.brzthook.mode.push() {
+        ctx.data()
+            .hook_listener
+            .subscribe(&author_id, Mode::Unsubscribe)
+            .await?;
+    }
 
-    queries::delete_sub(db, &author_id, ctx.guild_id().unwrap().get()).await?;
-
-    let content = format!("Unsubbed to {name}");
+    let content = format!("Unsubbed to {}", sub.yt_channel_name);
     ctx.say(&content).await?;
     Ok(())
 }
@@ -1,3 +1,13 @@
 pub const INVIDIOUS_INSTANCES_URL: &str = "https://api.invidious.io/instances.json?sort_by=health";
 pub const YOUTUBE_VIDEO_PREFIX: &str = "https://www.youtube.com/watch?v=";
-pub const EXPIRATION_DAYS: i64 = 5;
\ No newline at end of file
+pub const YOUTUBE_CHANNEL_PREFIX: &str = "https://www.youtube.com/channel/";
+pub const YOUTUBE_PLAYLIST_PREFIX: &str = "https://www.youtube.com/playlist?list=";
+pub const YOUTUBE_FEED_URL: &str = "https://www.youtube.com/feeds/videos.xml?channel_id=";
+pub const YOUTUBE_THUMBNAIL_URL: &str = "https://i.ytimg.com/vi/{id}/hqdefault.jpg";
+pub const EXPIRATION_DAYS: i64 = 5;
+pub const DEFAULT_TEMPLATE: &str = "New video from **{channel}** !!!\n{url}";
+pub const DEFAULT_SHORT_TEMPLATE: &str = "New short from **{channel}** !!!\n{url}";
+pub const DEFAULT_PREMIERE_TEMPLATE: &str = "Upcoming premiere from **{channel}** !!!\n{url}";
+pub const DEFAULT_LIVE_TEMPLATE: &str = "**{channel}** is live !!!\n{url}";
+/// Longest duration of a Short, in seconds
+pub const SHORT_MAX_SECONDS: u64 = 180;
\ No newline at end of file
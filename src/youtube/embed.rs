@@ -0,0 +1,59 @@
+use brzthook::prelude::Notification;
+use poise::serenity_prelude::{
+    CreateActionRow, CreateButton, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, Timestamp,
+};
+use std::time::Duration;
+use tracing::{instrument, warn};
+
+use super::constants::{YOUTUBE_THUMBNAIL_URL, YOUTUBE_VIDEO_PREFIX};
+
+pub(super) const YOUTUBE_RED: u32 = 0xFF_00_00;
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Embed and link button announcing a video
+#[derive(Debug, Clone)]
+pub struct VideoEmbed {
+    pub embed: CreateEmbed,
+    pub buttons: CreateActionRow,
+}
+
+/// Build the embed of the video, with its thumbnail
+///
+/// Returns `None` if the thumbnail cannot be found, e.g. the video is not processed yet,
+/// so the video is announced with a text message instead.
+#[instrument(skip_all, fields(video_id = %notification.video_id))]
+pub async fn build(notification: &Notification) -> Option<VideoEmbed> {
+    let thumbnail = YOUTUBE_THUMBNAIL_URL.replace("{id}", &notification.video_id);
+    if let Err(e) = check_thumbnail(&thumbnail).await {
+        warn!("No thumbnail for {}: {e}", &notification.video_id);
+        return None;
+    }
+
+    let url = format!("{YOUTUBE_VIDEO_PREFIX}{}", &notification.video_id);
+    let mut embed = CreateEmbed::new()
+        .title(&notification.video_title)
+        .url(&url)
+        .author(CreateEmbedAuthor::new(&notification.channel_name).url(&notification.channel_url))
+        .image(thumbnail)
+        .colour(YOUTUBE_RED)
+        .footer(CreateEmbedFooter::new("YouTube"));
+    // Serenity timestamps are built from chrono types, not from `time` ones
+    if let Ok(published) = Timestamp::from_unix_timestamp(notification.published.unix_timestamp()) {
+        embed = embed.timestamp(published);
+    }
+    let buttons =
+        CreateActionRow::Buttons(vec![CreateButton::new_link(url).label("Watch on YouTube")]);
+
+    Some(VideoEmbed { embed, buttons })
+}
+
+async fn check_thumbnail(url: &str) -> Result<(), reqwest::Error> {
+    reqwest::Client::builder()
+        .timeout(LOOKUP_TIMEOUT)
+        .build()?
+        .head(url)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
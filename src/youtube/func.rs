@@ -1,74 +1,176 @@
-use poise::serenity_prelude::futures::{self, Stream, StreamExt};
-use serde_json::Value;
-use tracing::warn;
+use poise::{
+    serenity_prelude::{self as serenity, AutocompleteChoice, ChannelId, Mentionable},
+    CreateReply,
+};
+use std::{collections::HashSet, time::Duration};
 
-use super::{constants::INVIDIOUS_INSTANCES_URL, queries};
+use super::{
+    constants::YOUTUBE_CHANNEL_PREFIX, embed::YOUTUBE_RED, invidious::Channel,
+    models::SubYtChannel, queries, resolver,
+};
 use crate::{Context, Error};
 
-pub async fn autocomplete_sublist<'a>(
-    ctx: Context<'_>,
-    partial: &'a str,
-) -> impl Stream<Item = String> + 'a {
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+const CONFIRM_BUTTON: &str = "sub_confirm";
+const CANCEL_BUTTON: &str = "sub_cancel";
+
+/// Youtube channels the guild is subscribed to, shown by name with their id as value
+pub async fn autocomplete_sublist(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return vec![];
+    };
     let db = &ctx.data().db;
-    let subs_list = queries::get_subs_list(db).await.unwrap();
-    futures::stream::iter(subs_list).map(|sub| sub.yt_channel_name)
+    let subs = queries::get_guild_subs(db, guild_id.get())
+        .await
+        .unwrap_or_default();
+
+    let partial = partial.to_lowercase();
+    let mut yt_channel_ids = HashSet::new();
+    subs.into_iter()
+        .filter(|sub| sub.yt_channel_name.to_lowercase().contains(&partial))
+        // A channel posted in several places is listed once
+        .filter(|sub| yt_channel_ids.insert(sub.yt_channel_id.clone()))
+        .take(25)
+        .map(|sub| AutocompleteChoice::new(sub.yt_channel_name, sub.yt_channel_id))
+        .collect()
 }
 
-pub(super) async fn get_invidious_instances() -> Result<Option<Vec<Value>>, Error> {
-    let response = reqwest::get(INVIDIOUS_INSTANCES_URL).await?.text().await?;
-    let instances: serde_json::Value = serde_json::from_str(&response)?;
+/// Find the subscription of the guild to `name`, the name or id of the Youtube channel
+///
+/// A channel posted in several Discord channels needs `post_channel` to tell which one.
+/// Tells the user and returns `None` if no subscription matches.
+pub async fn find_sub(
+    ctx: &Context<'_>,
+    name: &str,
+    post_channel: Option<ChannelId>,
+) -> Result<Option<SubYtChannel>, Error> {
+    let guild_id = ctx.guild_id().ok_or("Not in guild")?;
+    let subs = queries::get_guild_subs(&ctx.data().db, guild_id.get())
+        .await?
+        .into_iter()
+        .filter(|sub| sub.yt_channel_id == name || sub.yt_channel_name.eq_ignore_ascii_case(name))
+        .collect::<Vec<_>>();
 
-    // Keep only instance that have available api calls
-    let instances = instances.as_array().map(|instances| {
-        instances
-            .iter()
-            .filter(|inst| inst[1]["api"] == true)
-            .cloned()
-            .collect()
-    });
+    let sub = match (post_channel, subs.as_slice()) {
+        (_, []) => {
+            ctx.say("No channel found with this name.").await?;
+            None
+        }
+        (None, [sub]) => Some(sub.clone()),
+        (None, [sub, ..]) => {
+            let post_channels = subs
+                .iter()
+                .map(|sub| ChannelId::new(sub.post_channel_id).mention().to_string())
+                .collect::<Vec<_>>();
+            ctx.say(format!(
+                "{} is posted in {}, choose one with the channel option",
+                sub.yt_channel_name,
+                post_channels.join(", ")
+            ))
+            .await?;
+            None
+        }
+        (Some(post_channel), [sub, ..]) => {
+            let found = subs
+                .iter()
+                .find(|sub| sub.post_channel_id == post_channel.get())
+                .cloned();
+            if found.is_none() {
+                ctx.say(format!(
+                    "{} is not posted in {}",
+                    sub.yt_channel_name,
+                    post_channel.mention()
+                ))
+                .await?;
+            }
+            found
+        }
+    };
 
-    Ok(instances)
+    Ok(sub)
 }
 
-pub async fn get_name_id(ctx: &Context<'_>, url: &str) -> Result<Option<(String, String)>, Error> {
-    let Some(instances) = get_invidious_instances().await? else {
-        warn!("No invidious instance found");
+/// Find the Youtube channel at `url`, which can be any address of the channel or of one
+/// of its videos
+///
+/// Says why to the author if none is found.
+pub async fn resolve_channel(ctx: &Context<'_>, url: &str) -> Result<Option<Channel>, Error> {
+    let Some(channel_ref) = resolver::parse(url) else {
+        ctx.say("Invalid input, expected the address of a Youtube channel or video")
+            .await?;
         return Ok(None);
     };
-    // If the input is the full address https://www.youtube.com/{suffix}
-    let suffix = url.rsplit_once('/').map_or(url, |tuple| tuple.1);
 
-    // The Youtube channel id starts with "UC", we can call directly the channel endpoint
-    // If suffix starts with "@", we use the search endpoint to find the channel with that name
-    // (assuming the first result is the good one)
-    let query = if suffix.starts_with("UC") {
-        format!("/api/v1/channels/{suffix}")
-    } else if suffix.starts_with('@') {
-        format!("/api/v1/search?q={suffix}&type=channel")
-    } else {
-        ctx.say("Invalid input").await?;
-        return Ok(None);
-    };
+    let channel = resolver::resolve(&ctx.data().invidious, &channel_ref).await?;
+    if channel.is_none() {
+        ctx.say("No channel found").await?;
+    }
+    Ok(channel)
+}
+
+/// Ask the author to confirm `channel` by its name and avatar, before subscribing to it
+///
+/// Returns `false` if cancelled or not confirmed in time.
+pub async fn confirm_channel(ctx: &Context<'_>, channel: &Channel) -> Result<bool, Error> {
+    let mut embed = serenity::CreateEmbed::new()
+        .title(&channel.author)
+        .url(format!("{YOUTUBE_CHANNEL_PREFIX}{}", channel.author_id))
+        .description("Subscribe to this channel?")
+        .colour(YOUTUBE_RED);
+    if let Some(avatar_url) = channel.avatar_url() {
+        embed = embed.thumbnail(avatar_url);
+    }
+    let buttons = serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(CONFIRM_BUTTON)
+            .label("Subscribe")
+            .style(serenity::ButtonStyle::Success),
+        serenity::CreateButton::new(CANCEL_BUTTON)
+            .label("Cancel")
+            .style(serenity::ButtonStyle::Secondary),
+    ]);
 
-    let instance_uri = instances[0][1]["uri"].to_string();
-    let instance_uri = instance_uri.trim_matches('"');
-    let query_url = format!("{instance_uri}{query}");
-    let response: Value = reqwest::get(&query_url).await?.json().await?;
-    let (author_name, author_id) = if suffix.starts_with('@') {
-        (
-            response[0]["author"]
-                .as_str()
-                .ok_or("No author name found")?,
-            response[0]["authorId"]
-                .as_str()
-                .ok_or("No authorId found")?,
+    let handle = ctx
+        .send(
+            CreateReply::default()
+                .embed(embed)
+                .components(vec![buttons]),
         )
+        .await?;
+    let m = handle.message().await?;
+
+    let Some(interaction) = m
+        .await_component_interaction(&ctx.serenity_context().shard)
+        .author_id(ctx.author().id)
+        .timeout(CONFIRM_TIMEOUT)
+        .await
+    else {
+        handle
+            .edit(
+                *ctx,
+                CreateReply::default()
+                    .content("Timed out")
+                    .components(vec![]),
+            )
+            .await?;
+        return Ok(false);
+    };
+
+    let confirmed = interaction.data.custom_id == CONFIRM_BUTTON;
+    // Keep the channel shown above the outcome of the subscription
+    let response = if confirmed {
+        serenity::CreateInteractionResponseMessage::new().components(vec![])
     } else {
-        (
-            response["author"].as_str().ok_or("No author found")?,
-            response["authorId"].as_str().ok_or("No authorId found")?,
-        )
+        serenity::CreateInteractionResponseMessage::new()
+            .content("Cancelled")
+            .embeds(vec![])
+            .components(vec![])
     };
+    interaction
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(response),
+        )
+        .await?;
 
-    Ok(Some((author_name.to_owned(), author_id.to_owned())))
+    Ok(confirmed)
 }
@@ -0,0 +1,629 @@
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, warn};
+
+use super::{
+    constants::{INVIDIOUS_INSTANCES_URL, SHORT_MAX_SECONDS},
+    models::VideoKind,
+};
+use crate::Error;
+
+/// Time the list of instances is kept before being downloaded again
+const INSTANCES_TTL: Duration = Duration::from_secs(6 * 3600);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of instances tried for one request
+const MAX_ATTEMPTS: usize = 3;
+/// Delay before the second attempt, doubled on each next one
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+/// Time an instance is left aside after a failure, doubled on each consecutive one
+const FAILURE_COOLDOWN: Duration = Duration::from_secs(60);
+const MAX_COOLDOWN: Duration = Duration::from_secs(3600);
+
+/// Where the instances come from
+#[derive(Debug)]
+enum Source {
+    /// List of public instances, in the format of api.invidious.io
+    List(String),
+    /// A single instance
+    Fixed(String),
+}
+
+#[derive(Debug, Default)]
+struct Instances {
+    uris: Vec<String>,
+    fetched_at: Option<Instant>,
+    failures: HashMap<String, Failure>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Failure {
+    /// Consecutive failures
+    count: u32,
+    /// The instance is not used before then, unless every instance failed
+    until: Instant,
+}
+
+/// Client of the Invidious API, shared by the youtube module
+///
+/// Requests go to the healthy instances in the order of the list, the ones failing are
+/// left aside for a while.
+#[derive(Debug)]
+pub struct InvidiousClient {
+    http: reqwest::Client,
+    source: Source,
+    instances: Mutex<Instances>,
+}
+
+impl Default for InvidiousClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InvidiousClient {
+    /// Client of the public instances, ranked by health
+    pub fn new() -> Self {
+        Self::with_source(Source::List(INVIDIOUS_INSTANCES_URL.to_string()))
+    }
+
+    /// Client of the instances listed at `instances_url`
+    pub fn with_instances_url(instances_url: impl Into<String>) -> Self {
+        Self::with_source(Source::List(instances_url.into()))
+    }
+
+    /// Client of the single instance at `base_url`, e.g. a local stub server
+    pub fn with_base_url(base_url: impl Into<String>) -> Self {
+        Self::with_source(Source::Fixed(base_url.into()))
+    }
+
+    fn with_source(source: Source) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            http,
+            source,
+            instances: Mutex::default(),
+        }
+    }
+
+    /// Get the details of a video
+    pub async fn video(&self, video_id: &str) -> Result<Video, Error> {
+        self.get(&format!(
+            "/api/v1/videos/{video_id}?fields=videoId,title,author,authorId,lengthSeconds,viewCount,liveNow,isUpcoming,adaptiveFormats"
+        ))
+        .await
+    }
+
+    /// Get the details of a channel by its id
+    pub async fn channel(&self, channel_id: &str) -> Result<Channel, Error> {
+        self.get(&format!("/api/v1/channels/{channel_id}")).await
+    }
+
+    /// Search videos, channels or playlists, as `kind`
+    ///
+    /// `duration` only filters videos.
+    pub async fn search(
+        &self,
+        query: &str,
+        kind: SearchKind,
+        duration: Option<SearchDuration>,
+    ) -> Result<Vec<SearchItem>, Error> {
+        // The serializer is not `Send`, and must be dropped before the await
+        let params = {
+            let mut params = url::form_urlencoded::Serializer::new(String::new());
+            params
+                .append_pair("q", query)
+                .append_pair("type", kind.as_param());
+            if let Some(duration) = duration {
+                params.append_pair("duration", duration.as_param());
+            }
+            params.finish()
+        };
+        self.get(&format!("/api/v1/search?{params}")).await
+    }
+
+    /// Find what a YouTube address points to, e.g. the channel of a handle
+    pub async fn resolve_url(&self, url: &str) -> Result<ResolvedUrl, Error> {
+        let params = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("url", url)
+            .finish();
+        self.get(&format!("/api/v1/resolveurl?{params}")).await
+    }
+
+    /// GET `path` on the healthy instances until one answers
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        let instances = self.healthy_instances().await?;
+        let mut last_error: Error = "No Invidious instance available".into();
+
+        for (attempt, uri) in instances.iter().take(MAX_ATTEMPTS).enumerate() {
+            if attempt > 0 {
+                tokio::time::sleep(RETRY_DELAY * 2_u32.pow(attempt as u32 - 1)).await;
+            }
+            let url = format!("{uri}{path}");
+            debug!("GET {url}");
+
+            let response = match self.http.get(&url).send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    warn!("GET {url}: {e}");
+                    self.record_failure(uri);
+                    last_error = e.into();
+                    continue;
+                }
+            };
+            let status = response.status();
+            if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+                // Another instance would answer the same
+                self.record_success(uri);
+                return Err(format!("GET {url}: {status}").into());
+            }
+            match response.error_for_status() {
+                Ok(response) => match response.json().await {
+                    Ok(json) => {
+                        self.record_success(uri);
+                        return Ok(json);
+                    }
+                    Err(e) => {
+                        warn!("GET {url}: {e}");
+                        self.record_failure(uri);
+                        last_error = e.into();
+                    }
+                },
+                Err(e) => {
+                    warn!("GET {url}: {e}");
+                    self.record_failure(uri);
+                    last_error = e.into();
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Instances to try, the ones that failed lately last
+    async fn healthy_instances(&self) -> Result<Vec<String>, Error> {
+        let uris = match &self.source {
+            Source::Fixed(base_url) => vec![base_url.clone()],
+            Source::List(instances_url) => self.cached_instances(instances_url).await?,
+        };
+
+        let now = Instant::now();
+        let instances = self.instances.lock().unwrap();
+        let (failing, healthy): (Vec<_>, Vec<_>) = uris.into_iter().partition(|uri| {
+            instances
+                .failures
+                .get(uri)
+                .is_some_and(|failure| failure.until > now)
+        });
+
+        Ok(healthy.into_iter().chain(failing).collect())
+    }
+
+    async fn cached_instances(&self, instances_url: &str) -> Result<Vec<String>, Error> {
+        {
+            let instances = self.instances.lock().unwrap();
+            if instances
+                .fetched_at
+                .is_some_and(|fetched_at| fetched_at.elapsed() < INSTANCES_TTL)
+            {
+                return Ok(instances.uris.clone());
+            }
+        }
+
+        let fetched = self.fetch_instances(instances_url).await;
+        let mut instances = self.instances.lock().unwrap();
+        match fetched {
+            Ok(uris) => {
+                info!("{} Invidious instances available", uris.len());
+                instances.uris = uris;
+                instances.fetched_at = Some(Instant::now());
+            }
+            // Better an outdated list than none
+            Err(e) if !instances.uris.is_empty() => {
+                warn!("Cannot update the Invidious instances: {e}");
+            }
+            Err(e) => return Err(e),
+        }
+
+        Ok(instances.uris.clone())
+    }
+
+    async fn fetch_instances(&self, instances_url: &str) -> Result<Vec<String>, Error> {
+        let instances: Vec<(String, Instance)> = self
+            .http
+            .get(instances_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // Keep only instance that have available api calls
+        Ok(instances
+            .into_iter()
+            .filter(|(_, instance)| instance.api == Some(true))
+            .map(|(_, instance)| instance.uri.trim_end_matches('/').to_string())
+            .collect())
+    }
+
+    fn record_success(&self, uri: &str) {
+        self.instances.lock().unwrap().failures.remove(uri);
+    }
+
+    fn record_failure(&self, uri: &str) {
+        let mut instances = self.instances.lock().unwrap();
+        let count = instances
+            .failures
+            .get(uri)
+            .map_or(0, |failure| failure.count)
+            + 1;
+        let cooldown = FAILURE_COOLDOWN
+            .saturating_mul(2_u32.saturating_pow(count - 1))
+            .min(MAX_COOLDOWN);
+        instances.failures.insert(
+            uri.to_string(),
+            Failure {
+                count,
+                until: Instant::now() + cooldown,
+            },
+        );
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Instance {
+    uri: String,
+    api: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Video {
+    pub video_id: String,
+    pub title: String,
+    pub author: String,
+    pub author_id: String,
+    pub length_seconds: u64,
+    #[serde(default)]
+    pub view_count: u64,
+    #[serde(default)]
+    pub live_now: bool,
+    #[serde(default)]
+    pub is_upcoming: bool,
+    #[serde(default)]
+    pub adaptive_formats: Vec<Format>,
+}
+
+impl Video {
+    /// Find out whether the video is an upload, a Short, a premiere or a live stream
+    pub fn kind(&self) -> VideoKind {
+        if self.live_now {
+            return VideoKind::Live;
+        }
+        if self.is_upcoming {
+            // A premiere is an uploaded video, a scheduled stream has no length yet
+            return if self.length_seconds > 0 {
+                VideoKind::Premiere
+            } else {
+                VideoKind::Live
+            };
+        }
+
+        // Shorts are the short videos in portrait orientation
+        let is_portrait = self
+            .adaptive_formats
+            .iter()
+            .filter_map(|format| format.size.as_deref()?.split_once('x'))
+            .filter_map(|(width, height)| {
+                Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?))
+            })
+            .any(|(width, height)| height > width);
+        if self.length_seconds > 0 && self.length_seconds <= SHORT_MAX_SECONDS && is_portrait {
+            VideoKind::Short
+        } else {
+            VideoKind::Upload
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Format {
+    /// `widthxheight` of the video formats
+    pub size: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Channel {
+    pub author: String,
+    pub author_id: String,
+    #[serde(default)]
+    pub author_thumbnails: Vec<Thumbnail>,
+}
+
+impl Channel {
+    /// Largest avatar of the channel
+    pub fn avatar_url(&self) -> Option<String> {
+        let thumbnail = self.author_thumbnails.iter().max_by_key(|t| t.width)?;
+        // Some instances give protocol-relative addresses
+        Some(if thumbnail.url.starts_with("//") {
+            format!("https:{}", thumbnail.url)
+        } else {
+            thumbnail.url.clone()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Thumbnail {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Type of the results of a search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum SearchKind {
+    Video,
+    Channel,
+    Playlist,
+}
+
+impl SearchKind {
+    fn as_param(self) -> &'static str {
+        match self {
+            Self::Video => "video",
+            Self::Channel => "channel",
+            Self::Playlist => "playlist",
+        }
+    }
+}
+
+/// Length filter of a video search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum SearchDuration {
+    #[name = "Short (under 4 minutes)"]
+    Short,
+    #[name = "Medium (4 to 20 minutes)"]
+    Medium,
+    #[name = "Long (over 20 minutes)"]
+    Long,
+}
+
+impl SearchDuration {
+    fn as_param(self) -> &'static str {
+        match self {
+            Self::Short => "short",
+            Self::Medium => "medium",
+            Self::Long => "long",
+        }
+    }
+}
+
+/// Result of a search
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SearchItem {
+    Video(VideoItem),
+    Channel(ChannelItem),
+    Playlist(PlaylistItem),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoItem {
+    pub video_id: String,
+    pub title: String,
+    pub author: String,
+    pub author_id: String,
+    #[serde(default)]
+    pub length_seconds: u64,
+    #[serde(default)]
+    pub view_count: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelItem {
+    pub author: String,
+    pub author_id: String,
+    #[serde(default)]
+    pub author_thumbnails: Vec<Thumbnail>,
+    /// `@handle` of the channel, not given by every instance
+    #[serde(default)]
+    pub channel_handle: Option<String>,
+    #[serde(default)]
+    pub sub_count: u64,
+    #[serde(default)]
+    pub video_count: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItem {
+    pub playlist_id: String,
+    pub title: String,
+    pub author: String,
+    pub author_id: String,
+    #[serde(default)]
+    pub video_count: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedUrl {
+    /// Id of the channel, when the address is one
+    pub ucid: Option<String>,
+    pub page_type: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Serve `routes`, as `(path prefix, status, body)`, and count the requests
+    async fn stub_server(routes: Vec<(&'static str, u16, String)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&hits);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut buf = vec![0; 4096];
+                let len = stream.read(&mut buf).await.unwrap_or_default();
+                let request = String::from_utf8_lossy(&buf[..len]);
+                let path = request.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = routes
+                    .iter()
+                    .find(|(prefix, _, _)| path.starts_with(prefix))
+                    .map_or((404, String::new()), |(_, status, body)| {
+                        (*status, body.clone())
+                    });
+                let response = format!(
+                    "HTTP/1.1 {status} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (base_url, hits)
+    }
+
+    /// Address nothing listens on
+    async fn dead_url() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    fn video_json() -> String {
+        json!({
+            "videoId": "dQw4w9WgXcQ",
+            "title": "Tips & Tricks",
+            "author": "Linus Tech Tips",
+            "authorId": "UCXuqSBlHAE6Xw-yeJA0Tunw",
+            "lengthSeconds": 754,
+            "viewCount": 1200,
+            "adaptiveFormats": [{ "size": "1920x1080" }, { "type": "audio/mp4" }]
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_next_instance() {
+        let (instance, _) = stub_server(vec![("/api/v1/videos/", 200, video_json())]).await;
+        let dead = dead_url().await;
+        let list = json!([
+            ["dead.example", { "uri": dead, "api": true }],
+            ["no-api.example", { "uri": "http://127.0.0.1:1", "api": false }],
+            ["stub.example", { "uri": instance, "api": true }]
+        ]);
+        let (instances_url, list_hits) = stub_server(vec![("/", 200, list.to_string())]).await;
+        let client = InvidiousClient::with_instances_url(instances_url);
+
+        let video = client.video("dQw4w9WgXcQ").await.unwrap();
+        assert_eq!(video.title, "Tips & Tricks");
+        assert_eq!(video.kind(), VideoKind::Upload);
+
+        // The dead instance is now tried last, and the list is cached
+        assert_eq!(
+            client.healthy_instances().await.unwrap(),
+            vec![instance, dead]
+        );
+        assert!(client.video("dQw4w9WgXcQ").await.is_ok());
+        assert_eq!(list_hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_not_found_is_not_a_failure() {
+        let (base_url, hits) = stub_server(vec![]).await;
+        let client = InvidiousClient::with_base_url(&base_url);
+
+        assert!(client.channel("UCnope").await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert!(client.instances.lock().unwrap().failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_filters() {
+        let results = json!([
+            { "type": "video", "videoId": "dQw4w9WgXcQ", "title": "Tips & Tricks",
+              "author": "Linus Tech Tips", "authorId": "UCXuqSBlHAE6Xw-yeJA0Tunw" },
+            { "type": "playlist", "playlistId": "PL8mG-RkN2uTw7PhlnAr4pZZz2QubIbujH",
+              "title": "Tech Tips", "author": "Linus Tech Tips",
+              "authorId": "UCXuqSBlHAE6Xw-yeJA0Tunw", "videoCount": 12 },
+            { "type": "hashtag", "title": "#tips" }
+        ]);
+        let (base_url, _) = stub_server(vec![(
+            "/api/v1/search?q=tips+%26+tricks&type=video&duration=short",
+            200,
+            results.to_string(),
+        )])
+        .await;
+        let client = InvidiousClient::with_base_url(&base_url);
+
+        let items = client
+            .search(
+                "tips & tricks",
+                SearchKind::Video,
+                Some(SearchDuration::Short),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(&items[0], SearchItem::Video(video) if video.length_seconds == 0));
+        assert!(matches!(&items[1], SearchItem::Playlist(playlist) if playlist.video_count == 12));
+        assert!(matches!(items[2], SearchItem::Other));
+    }
+
+    #[test]
+    fn test_video_kind() {
+        let video = |value: serde_json::Value| {
+            let mut video: serde_json::Value = serde_json::from_str(&video_json()).unwrap();
+            video
+                .as_object_mut()
+                .unwrap()
+                .extend(value.as_object().unwrap().clone());
+            serde_json::from_value::<Video>(video).unwrap()
+        };
+
+        assert_eq!(video(json!({})).kind(), VideoKind::Upload);
+        let short = video(json!({
+            "lengthSeconds": 42,
+            "adaptiveFormats": [{ "type": "audio/mp4" }, { "size": "1080x1920" }]
+        }));
+        assert_eq!(short.kind(), VideoKind::Short);
+        assert_eq!(
+            video(json!({ "isUpcoming": true })).kind(),
+            VideoKind::Premiere
+        );
+        assert_eq!(
+            video(json!({ "lengthSeconds": 0, "isUpcoming": true })).kind(),
+            VideoKind::Live
+        );
+        assert_eq!(
+            video(json!({ "lengthSeconds": 0, "liveNow": true })).kind(),
+            VideoKind::Live
+        );
+    }
+}
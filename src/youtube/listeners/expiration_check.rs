@@ -1,47 +1,132 @@
 use brzthook::{HookListener, Mode};
 use poise::serenity_prelude::{self as serenity, ChannelId};
-use serde_json::Value;
-use std::{
-    sync::{mpsc, Arc},
-    time::Duration,
-};
+use std::{sync::Arc, time::Duration};
 use time::OffsetDateTime;
-use tokio::join;
-use tracing::{debug, error, info, instrument, warn};
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info, instrument, warn};
 
-use super::{constants::EXPIRATION_DAYS, queries};
-use crate::{database::Db, Context, Data, Error};
+use super::queries;
+use crate::{config::HookMode, database::Db, youtube::models::YtChannel, Data, Error};
 
-pub fn expiration_check_timer(listener: Arc<HookListener>, db: Arc<Db>) -> Result<(), Error> {
-    let rt = tokio::runtime::Runtime::new()?;
+/// Time between two checks of the subscriptions
+const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+/// Time before alerting again about a subscription still failing
+const ALERT_INTERVAL: time::Duration = time::Duration::days(1);
+
+/// Start [`expiration_check_loop`] in a separate task
+pub fn spawn_expiration_check(ctx: serenity::Context, data: &Data) {
+    let db = Arc::clone(&data.db);
+    let listener = Arc::clone(&data.hook_listener);
+    let log_channel = data.config.brzthook.log_channel.map(ChannelId::new);
+    let stale_after = time::Duration::days(data.config.brzthook.stale_days);
+    let mode = data.config.brzthook.mode;
+    tokio::spawn(async move {
+        expiration_check_loop(ctx, db, listener, log_channel, stale_after, mode).await;
+    });
+}
+
+/// Renew the subscriptions a day before they expire, and alert `log_channel` about the
+/// ones failing or without notification for `stale_after`
+///
+/// Nothing is renewed in `poll` mode, where the feeds not fetched for `stale_after` are
+/// the ones alerted about.
+///
+/// Errors are logged and the subscription retried on the next check.
+#[instrument(skip(ctx, db, listener))]
+pub async fn expiration_check_loop(
+    ctx: serenity::Context,
+    db: Arc<Db>,
+    listener: Arc<HookListener>,
+    log_channel: Option<ChannelId>,
+    stale_after: time::Duration,
+    mode: HookMode,
+) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
     loop {
+        interval.tick().await;
         info!("Checking for expiration");
-        let subs = rt.block_on(async { queries::get_subs_list(&db).await })?;
-        let mut resubbed = vec![];
-        let now_utc = OffsetDateTime::now_utc();
-
-        for sub in subs {
-            if now_utc > sub.expire_on.checked_add(time::Duration::days(-1)).unwrap()
-                && !resubbed.contains(&sub.yt_channel_id)
-            {
-                listener.subscribe(&sub.yt_channel_id, Mode::Subscribe)?;
-                let new_expire = now_utc
-                    .checked_add(time::Duration::days(EXPIRATION_DAYS))
-                    .unwrap();
-                rt.block_on(async {
-                    queries::update_expire_on(&db, new_expire, &sub.yt_channel_id).await
-                })?;
-
-                info!(
-                    "Renewed subscription for {}: new expire_on = {}",
-                    &sub.yt_channel_id, new_expire
-                );
-                resubbed.push(sub.yt_channel_id);
+
+        let yt_channels = match queries::get_yt_channels(&db).await {
+            Ok(yt_channels) => yt_channels,
+            Err(e) => {
+                error!("Cannot get the subscriptions: {e}");
+                continue;
+            }
+        };
+
+        for yt_channel in yt_channels {
+            let checked = check(
+                &ctx,
+                &db,
+                &listener,
+                log_channel,
+                stale_after,
+                mode,
+                yt_channel,
+            )
+            .await;
+            if let Err(e) = checked {
+                error!("in expiration check: {e}");
+            }
+        }
+    }
+}
+
+async fn check(
+    ctx: &serenity::Context,
+    db: &Db,
+    listener: &HookListener,
+    log_channel: Option<ChannelId>,
+    stale_after: time::Duration,
+    mode: HookMode,
+    mut yt_channel: YtChannel,
+) -> Result<(), Error> {
+    let now = OffsetDateTime::now_utc();
+    let id = &yt_channel.yt_channel_id;
+
+    if mode.push() && now > yt_channel.renew_on() {
+        // The new expiration date is set when the hub verifies the subscription
+        info!("Renewing subscription for {id}");
+        match listener.subscribe(id, Mode::Subscribe).await {
+            Ok(_) => {
+                // A secret is generated for the subscriptions the listener has none for
+                if let Some(hub_secret) = listener.secret(id) {
+                    queries::update_hub_secret(db, &hub_secret, id).await?;
+                }
+                queries::update_renew_error(db, None, id).await?;
+                yt_channel.renew_error = None;
+            }
+            Err(e) => {
+                warn!("Cannot renew subscription for {id}: {e}");
+                let renew_error = e.to_string();
+                queries::update_renew_error(db, Some(&renew_error), id).await?;
+                yt_channel.renew_error = Some(renew_error);
+                // Alert right away about a new failure
+                yt_channel.alerted_on = None;
             }
         }
+    }
 
-        // Check once a day
-        std::thread::sleep(Duration::from_secs(24 * 3600));
+    let problems = yt_channel.problems(now, stale_after, mode);
+    let Some(log_channel) = log_channel else {
+        return Ok(());
+    };
+    let alerted_lately = yt_channel
+        .alerted_on
+        .is_some_and(|alerted_on| now - alerted_on < ALERT_INTERVAL);
+    if problems.is_empty() || alerted_lately {
+        return Ok(());
     }
+
+    let content = format!(
+        "⚠️ Subscription to **{}** ({id}):\n- {}",
+        yt_channel.yt_channel_name,
+        problems.join("\n- ")
+    );
+    log_channel.say(&ctx.http, content).await?;
+    queries::update_alerted_on(db, now, id).await?;
+
+    Ok(())
 }
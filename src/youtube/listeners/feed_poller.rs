@@ -0,0 +1,151 @@
+use brzthook::prelude::Event;
+use poise::serenity_prelude as serenity;
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, info, instrument, warn};
+
+use super::{constants::YOUTUBE_FEED_URL, hook_listener::publish, queries};
+use crate::{
+    database::Db,
+    youtube::{invidious::InvidiousClient, models::YtChannel},
+    Data, Error,
+};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Start [`poll_loop`] in a separate task
+pub fn spawn_poll_loop(ctx: serenity::Context, data: &Data) {
+    let db = Arc::clone(&data.db);
+    let invidious = Arc::clone(&data.invidious);
+    let interval = Duration::from_secs(data.config.brzthook.poll_interval);
+    let edit_on_update = data.config.brzthook.edit_on_update;
+    tokio::spawn(async move {
+        if let Err(e) = poll_loop(ctx, db, invidious, interval, edit_on_update).await {
+            error!("in poll_loop: {e}");
+        }
+    });
+}
+
+/// Fetch the RSS feed of every subscribed channel each `interval`, and announce the
+/// videos not seen yet, like [`listen_loop`](super::hook_listener::listen_loop) does
+/// for the pushed ones
+#[instrument(skip(ctx, invidious))]
+pub async fn poll_loop(
+    ctx: serenity::Context,
+    db: Arc<Db>,
+    invidious: Arc<InvidiousClient>,
+    interval: Duration,
+    edit_on_update: bool,
+) -> Result<(), Error> {
+    let client = reqwest::Client::builder().timeout(FETCH_TIMEOUT).build()?;
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Feeds fetched since the start of the loop
+    let mut polled = HashSet::new();
+
+    info!("Starting feeds polling");
+    loop {
+        interval.tick().await;
+
+        // Try again on the next tick
+        let yt_channels = match queries::get_yt_channels(&db).await {
+            Ok(yt_channels) => yt_channels,
+            Err(e) => {
+                error!("Cannot get the subscriptions to poll: {e}");
+                continue;
+            }
+        };
+
+        for yt_channel in yt_channels {
+            let first_poll = polled.insert(yt_channel.yt_channel_id.clone());
+            let polled_feed = poll_feed(
+                &ctx,
+                &db,
+                &invidious,
+                &client,
+                &yt_channel,
+                first_poll,
+                edit_on_update,
+            )
+            .await;
+            if let Err(e) = polled_feed {
+                warn!("Cannot poll the feed of {}: {e}", yt_channel.yt_channel_id);
+            }
+        }
+    }
+}
+
+/// Announce the videos of the feed of `yt_channel` that were not seen yet
+///
+/// On the first poll, the videos published before the bot last heard of the channel, by
+/// the hub, a poll or a video already seen, are only recorded as seen; all of them when
+/// it never did.
+async fn poll_feed(
+    ctx: &serenity::Context,
+    db: &Db,
+    invidious: &InvidiousClient,
+    client: &reqwest::Client,
+    yt_channel: &YtChannel,
+    first_poll: bool,
+    edit_on_update: bool,
+) -> Result<(), Error> {
+    let yt_channel_id = &yt_channel.yt_channel_id;
+    let xml = client
+        .get(format!("{YOUTUBE_FEED_URL}{yt_channel_id}"))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let notifications = Event::parse_feed(&xml)?
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::Published(notification) => Some(notification),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    queries::update_polled_on(db, yt_channel_id).await?;
+
+    let seen = queries::get_seen_video_ids(db, yt_channel_id).await?;
+    let backlog_until = if first_poll {
+        let latest_seen = notifications
+            .iter()
+            .filter(|notification| seen.contains(&notification.video_id))
+            .map(|notification| notification.published)
+            .max();
+        let last_heard = latest_seen
+            .max(yt_channel.notified_on)
+            .max(yt_channel.polled_on);
+        last_heard.or_else(|| {
+            notifications
+                .iter()
+                .map(|notification| notification.published)
+                .max()
+        })
+    } else {
+        None
+    };
+
+    // The feed lists the latest videos first
+    for notification in notifications.iter().rev() {
+        if seen.contains(&notification.video_id) {
+            continue;
+        }
+        if backlog_until.is_some_and(|until| notification.published <= until) {
+            debug!(
+                "Video {} published before the first poll of {yt_channel_id}",
+                notification.video_id
+            );
+            queries::insert_seen_video(db, yt_channel_id, &notification.video_id).await?;
+            continue;
+        }
+
+        info!("Found video {} in the feed", notification.video_id);
+        // One failed video must not stop the rest of the feed
+        if let Err(e) = publish(ctx, db, invidious, notification, edit_on_update).await {
+            error!("Cannot publish video {}: {e}", notification.video_id);
+        }
+    }
+
+    Ok(())
+}
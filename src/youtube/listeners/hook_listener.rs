@@ -1,55 +1,284 @@
-use brzthook::{HookListener, Mode};
-use poise::serenity_prelude::{self as serenity, ChannelId};
-use serde_json::Value;
-use std::{
-    sync::{mpsc, Arc},
-    time::Duration,
+use brzthook::{
+    prelude::{Event, Notification},
+    HookListener, Mode,
 };
-use time::OffsetDateTime;
-use tokio::join;
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, CreateAllowedMentions, CreateMessage, EditMessage, Mentionable,
+    MessageId, RoleId,
+};
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
 use tracing::{debug, error, info, instrument, warn};
 
-use super::{constants::YOUTUBE_VIDEO_PREFIX, queries};
-use crate::{database::Db, Context, Data, Error};
+use super::{
+    constants::{EXPIRATION_DAYS, YOUTUBE_VIDEO_PREFIX},
+    queries,
+};
+use crate::{
+    database::Db,
+    mention_roles,
+    youtube::{
+        embed::{self, VideoEmbed},
+        invidious::InvidiousClient,
+        models::{PendingSubs, SubOutcome, SubYtChannel, VideoKind},
+        template::{self, Announcement},
+    },
+    Data, Error,
+};
+
+/// Start [`listen_loop`] in a separate task
+pub fn spawn_listen_loop(ctx: serenity::Context, data: &Data) {
+    let db = Arc::clone(&data.db);
+    let listener = Arc::clone(&data.hook_listener);
+    let pending_subs = Arc::clone(&data.pending_subs);
+    let invidious = Arc::clone(&data.invidious);
+    let edit_on_update = data.config.brzthook.edit_on_update;
+    tokio::spawn(async move {
+        let listened =
+            listen_loop(ctx, db, listener, pending_subs, invidious, edit_on_update).await;
+        if let Err(e) = listened {
+            error!("in listen_loop: {e}");
+        }
+    });
+}
 
-#[instrument(skip(ctx))]
-pub fn listen_loop(
+#[instrument(skip(ctx, pending_subs, invidious))]
+pub async fn listen_loop(
     ctx: serenity::Context,
     db: Arc<Db>,
     listener: Arc<HookListener>,
+    pending_subs: PendingSubs,
+    invidious: Arc<InvidiousClient>,
+    edit_on_update: bool,
 ) -> Result<(), Error> {
-    // Start TCP listening in another thread and pass it a `Sender<Notification>
-    let (tx, rx) = mpsc::channel();
-    listener.listen(&tx);
+    // Start TCP listening in a separate task and get a `Receiver<Event>`
+    let mut rx = listener.listen()?;
 
     info!("Starting webhooks listener");
-    // Wait for the sender to transfer data
-    loop {
-        match rx.recv().expect("recv error") {
+    // Wait for the listener to transfer data
+    while let Some(message) = rx.recv().await {
+        match message {
             Err(e) => error!("Error in HookListener: {e}"),
-            Ok(notification) => {
-                let rt = tokio::runtime::Runtime::new()?;
-
-                info!("Got notification from listener");
-                let post_channel_ids = rt.block_on(async {
-                    queries::get_post_channel_ids(&db, &notification.channel_id)
-                        .await
-                        .unwrap()
+            Ok(Event::Verified {
+                id: Some(id),
+                mode: Mode::Subscribe,
+                lease_seconds,
+                ..
+            }) => {
+                // The hub grants the lease; fall back on the usual one if not told
+                let lease = lease_seconds.map_or(Duration::days(EXPIRATION_DAYS), |secs| {
+                    Duration::seconds(i64::try_from(secs).unwrap_or(i64::MAX))
                 });
+                let expire_on = OffsetDateTime::now_utc().saturating_add(lease);
+                if let Err(e) = queries::update_expire_on(&db, expire_on, &id).await {
+                    error!("Cannot save the expiration of {id}: {e}");
+                    continue;
+                }
+                info!("Subscription to {id} verified: expire_on = {expire_on}");
+
+                notify_pending(&pending_subs, &id, &SubOutcome::Verified { expire_on });
+            }
+            Ok(Event::Verified { id, mode, .. }) => {
+                info!("{mode} verified for {id:?}");
+            }
+            Ok(Event::Denied {
+                id: Some(id),
+                reason,
+                ..
+            }) => {
+                warn!("Subscription to {id} denied: {reason}");
+                if let Err(e) = queries::set_denied(&db, &reason, &id).await {
+                    error!("Cannot save the denial of {id}: {e}");
+                    continue;
+                }
 
-                for id in post_channel_ids {
-                    let message = format!("New video from **{}** !!!", &notification.channel_name);
-                    let content = format!(
-                        "{message}\n{YOUTUBE_VIDEO_PREFIX}{}",
-                        &notification.video_id
+                notify_pending(&pending_subs, &id, &SubOutcome::Denied { reason });
+            }
+            Ok(Event::Denied { topic, reason, .. }) => {
+                warn!("Subscription to unknown topic {topic} denied: {reason}");
+            }
+            Ok(Event::Deleted(deleted)) => {
+                if let Err(e) = queries::update_notified_on(&db, &deleted.channel_id).await {
+                    error!("Cannot update notified_on of {}: {e}", deleted.channel_id);
+                }
+                info!(
+                    "Video {} deleted from channel {}",
+                    deleted.video_id, deleted.channel_id
+                );
+            }
+            Ok(Event::Published(notification)) => {
+                info!("Got notification from listener");
+                if let Err(e) = queries::update_notified_on(&db, &notification.channel_id).await {
+                    error!(
+                        "Cannot update notified_on of {}: {e}",
+                        notification.channel_id
                     );
-                    // Bot disconnect after ChannelId.say(); why ?
-                    rt.block_on(async {
-                        ChannelId::new(id).say(&ctx.http, &content).await.unwrap();
-                    });
+                }
+                // One failed announcement must not stop the next notifications
+                if let Err(e) = publish(&ctx, &db, &invidious, &notification, edit_on_update).await
+                {
+                    error!("Cannot publish video {}: {e}", notification.video_id);
                 }
             }
         }
     }
     debug!("Quit listener loop");
+
+    Ok(())
+}
+
+/// Announce the video of `notification` in every guild subscribed to its channel
+///
+/// Entries pushed by the hub and entries found in the polled feeds both go through here.
+pub(super) async fn publish(
+    ctx: &serenity::Context,
+    db: &Db,
+    invidious: &InvidiousClient,
+    notification: &Notification,
+    edit_on_update: bool,
+) -> Result<(), Error> {
+    queries::insert_seen_video(db, &notification.channel_id, &notification.video_id).await?;
+    let subs = queries::get_subs_by_channel(db, &notification.channel_id).await?;
+    if subs.is_empty() {
+        return Ok(());
+    }
+
+    // Announce as a regular upload if Invidious cannot tell
+    let kind = match invidious.video(&notification.video_id).await {
+        Ok(video) => video.kind(),
+        Err(e) => {
+            warn!(
+                "Cannot get the kind of video {}: {e}",
+                notification.video_id
+            );
+            VideoKind::Upload
+        }
+    };
+    debug!("Video {} is a {kind:?}", notification.video_id);
+    let subs = subs
+        .into_iter()
+        .filter(|sub| sub.announces(kind))
+        .collect::<Vec<_>>();
+    if subs.is_empty() {
+        return Ok(());
+    }
+    let embed = embed::build(notification).await;
+
+    for sub in subs {
+        let announced = announce(
+            ctx,
+            db,
+            notification,
+            &sub,
+            kind,
+            embed.as_ref(),
+            edit_on_update,
+        )
+        .await;
+        if let Err(e) = announced {
+            error!(
+                "Cannot post notification in channel {}: {e}",
+                sub.post_channel_id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Post the video in the channel of the subscription, once.
+///
+/// The hub delivers the same entry again on every update of the video; the announcement
+/// is then edited if `edit_on_update` is set, or else left as is.
+///
+/// The video is shown in `embed` if any, or else by the preview of its link.
+async fn announce(
+    ctx: &serenity::Context,
+    db: &Db,
+    notification: &Notification,
+    sub: &SubYtChannel,
+    kind: VideoKind,
+    embed: Option<&VideoEmbed>,
+    edit_on_update: bool,
+) -> Result<(), Error> {
+    let post_channel_id = sub.post_channel_id;
+    let channel_id = ChannelId::new(post_channel_id);
+    let url = format!("{YOUTUBE_VIDEO_PREFIX}{}", &notification.video_id);
+    // Discord does not preview links between angle brackets
+    let url = match embed {
+        Some(_) => format!("<{url}>"),
+        None => url,
+    };
+
+    // The role may have been removed from the mention roles since the subscription
+    let role_id = match sub.mention_role_id {
+        Some(role_id)
+            if mention_roles::queries::get_role_ids(db, sub.guild_id)
+                .await?
+                .contains(&role_id) =>
+        {
+            Some(RoleId::new(role_id))
+        }
+        _ => None,
+    };
+    // Only ping the role of the subscription, whatever the template says
+    let allowed_mentions = CreateAllowedMentions::new().roles(role_id);
+
+    let announcement = Announcement {
+        channel: &notification.channel_name,
+        title: &notification.video_title,
+        url: &url,
+        published: notification.published,
+        role: role_id.map(|role_id| role_id.mention().to_string()),
+    };
+    let template = sub.template(kind).unwrap_or(kind.default_template());
+    let content = template::render(Some(template), &announcement);
+
+    // Claimed first, so that the listener and the poller cannot both post the video
+    let video_id = &notification.video_id;
+    if queries::claim_announcement(db, &notification.channel_id, video_id, post_channel_id).await? {
+        let mut message = CreateMessage::new()
+            .content(content)
+            .allowed_mentions(allowed_mentions);
+        if let Some(VideoEmbed { embed, buttons }) = embed.cloned() {
+            message = message.embed(embed).components(vec![buttons]);
+        }
+        let posted = match channel_id.send_message(&ctx.http, message).await {
+            Ok(posted) => posted,
+            Err(e) => {
+                // Let the next delivery of the video try again
+                queries::delete_announcement(db, video_id, post_channel_id).await?;
+                return Err(e.into());
+            }
+        };
+        queries::set_announcement_message(db, video_id, post_channel_id, posted.id.get()).await?;
+    } else if edit_on_update {
+        match queries::get_announcement(db, video_id, post_channel_id).await? {
+            Some(message_id) => {
+                let mut message = EditMessage::new()
+                    .content(content)
+                    .allowed_mentions(allowed_mentions);
+                if let Some(VideoEmbed { embed, buttons }) = embed.cloned() {
+                    message = message.embed(embed).components(vec![buttons]);
+                }
+                channel_id
+                    .edit_message(&ctx.http, MessageId::new(message_id), message)
+                    .await?;
+            }
+            None => debug!("Video {video_id} being announced in {post_channel_id}; pass"),
+        }
+    } else {
+        debug!("Video {video_id} already announced in {post_channel_id}; pass");
+    }
+
+    Ok(())
+}
+
+/// Tell the commands waiting for the subscription to `yt_channel_id` the answer of the hub
+fn notify_pending(pending_subs: &PendingSubs, yt_channel_id: &str, outcome: &SubOutcome) {
+    let waiting = pending_subs.lock().unwrap().remove(yt_channel_id);
+    for sender in waiting.into_iter().flatten() {
+        // The command may have stopped waiting
+        let _ = sender.send(outcome.clone());
+    }
 }
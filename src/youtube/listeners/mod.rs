@@ -1,4 +1,5 @@
 pub mod expiration_check;
+pub mod feed_poller;
 pub mod hook_listener;
 
 use super::{constants, queries};
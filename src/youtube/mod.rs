@@ -1,8 +1,16 @@
 pub mod commands;
 pub mod constants;
+pub mod embed;
 pub mod func;
+pub mod invidious;
 pub mod listeners;
 pub mod models;
 pub mod queries;
+pub mod resolver;
+pub mod template;
 
-pub use listeners::{expiration_check::expiration_check_timer, hook_listener::listen_loop};
+pub use listeners::{
+    expiration_check::{expiration_check_loop, spawn_expiration_check},
+    feed_poller::{poll_loop, spawn_poll_loop},
+    hook_listener::{listen_loop, spawn_listen_loop},
+};
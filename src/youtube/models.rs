@@ -1,4 +1,17 @@
-use crate::database::{from_i64, to_i64};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use time::{Duration, OffsetDateTime};
+use tokio::sync::oneshot;
+
+use super::constants::{
+    DEFAULT_LIVE_TEMPLATE, DEFAULT_PREMIERE_TEMPLATE, DEFAULT_SHORT_TEMPLATE, DEFAULT_TEMPLATE,
+};
+use crate::{
+    config::HookMode,
+    database::{from_i64, to_i64},
+};
 
 #[derive(Debug, Clone)]
 pub struct SubYtChannel {
@@ -7,6 +20,64 @@ pub struct SubYtChannel {
     pub guild_id: u64,
     pub post_channel_id: u64,
     pub expire_on: time::OffsetDateTime,
+    pub hub_secret: Option<String>,
+    pub denied_reason: Option<String>,
+    pub template: Option<String>,
+    pub mention_role_id: Option<u64>,
+    pub announce_uploads: bool,
+    pub announce_shorts: bool,
+    pub announce_premieres: bool,
+    pub announce_lives: bool,
+    pub template_short: Option<String>,
+    pub template_premiere: Option<String>,
+    pub template_live: Option<String>,
+}
+
+impl SubYtChannel {
+    /// Whether the videos of this kind are announced
+    pub fn announces(&self, kind: VideoKind) -> bool {
+        match kind {
+            VideoKind::Upload => self.announce_uploads,
+            VideoKind::Short => self.announce_shorts,
+            VideoKind::Premiere => self.announce_premieres,
+            VideoKind::Live => self.announce_lives,
+        }
+    }
+
+    /// Announcement template for the videos of this kind
+    ///
+    /// The template of the uploads is used for the kinds without their own.
+    pub fn template(&self, kind: VideoKind) -> Option<&str> {
+        let template = match kind {
+            VideoKind::Upload => None,
+            VideoKind::Short => self.template_short.as_deref(),
+            VideoKind::Premiere => self.template_premiere.as_deref(),
+            VideoKind::Live => self.template_live.as_deref(),
+        };
+        template.or(self.template.as_deref())
+    }
+}
+
+/// What a published entry of a channel is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum VideoKind {
+    Upload,
+    Short,
+    Premiere,
+    #[name = "Live stream"]
+    Live,
+}
+
+impl VideoKind {
+    /// Template used when the subscription has none
+    pub fn default_template(self) -> &'static str {
+        match self {
+            Self::Upload => DEFAULT_TEMPLATE,
+            Self::Short => DEFAULT_SHORT_TEMPLATE,
+            Self::Premiere => DEFAULT_PREMIERE_TEMPLATE,
+            Self::Live => DEFAULT_LIVE_TEMPLATE,
+        }
+    }
 }
 
 #[allow(unused)]
@@ -24,6 +95,17 @@ pub struct SubYtChannelSQL {
     pub guild_id: i64,
     pub post_channel_id: i64,
     pub expire_on: time::OffsetDateTime,
+    pub hub_secret: Option<String>,
+    pub denied_reason: Option<String>,
+    pub template: Option<String>,
+    pub mention_role_id: Option<i64>,
+    pub announce_uploads: bool,
+    pub announce_shorts: bool,
+    pub announce_premieres: bool,
+    pub announce_lives: bool,
+    pub template_short: Option<String>,
+    pub template_premiere: Option<String>,
+    pub template_live: Option<String>,
 }
 
 impl From<SubYtChannel> for SubYtChannelSQL {
@@ -34,6 +116,17 @@ impl From<SubYtChannel> for SubYtChannelSQL {
             guild_id: to_i64(value.guild_id),
             post_channel_id: to_i64(value.post_channel_id),
             expire_on: value.expire_on,
+            hub_secret: value.hub_secret,
+            denied_reason: value.denied_reason,
+            template: value.template,
+            mention_role_id: value.mention_role_id.map(to_i64),
+            announce_uploads: value.announce_uploads,
+            announce_shorts: value.announce_shorts,
+            announce_premieres: value.announce_premieres,
+            announce_lives: value.announce_lives,
+            template_short: value.template_short,
+            template_premiere: value.template_premiere,
+            template_live: value.template_live,
         }
     }
 }
@@ -46,6 +139,187 @@ impl From<SubYtChannelSQL> for SubYtChannel {
             guild_id: from_i64(value.guild_id),
             post_channel_id: from_i64(value.post_channel_id),
             expire_on: value.expire_on,
+            hub_secret: value.hub_secret,
+            denied_reason: value.denied_reason,
+            template: value.template,
+            mention_role_id: value.mention_role_id.map(from_i64),
+            announce_uploads: value.announce_uploads,
+            announce_shorts: value.announce_shorts,
+            announce_premieres: value.announce_premieres,
+            announce_lives: value.announce_lives,
+            template_short: value.template_short,
+            template_premiere: value.template_premiere,
+            template_live: value.template_live,
+        }
+    }
+}
+
+/// State of the hub subscription to a Youtube channel, shared by all its targets
+#[derive(Debug, Clone)]
+pub struct YtChannel {
+    pub yt_channel_id: String,
+    pub yt_channel_name: String,
+    pub expire_on: OffsetDateTime,
+    /// Last verification of intent of a subscription request
+    pub verified_on: Option<OffsetDateTime>,
+    /// Last notification pushed by the hub
+    pub notified_on: Option<OffsetDateTime>,
+    /// Last fetch of the channel feed
+    pub polled_on: Option<OffsetDateTime>,
+    pub denied_reason: Option<String>,
+    /// Error of the last renewal request, if it failed
+    pub renew_error: Option<String>,
+    /// Last alert sent to the log channel about this channel
+    pub alerted_on: Option<OffsetDateTime>,
+}
+
+impl YtChannel {
+    /// When the subscription is renewed, a day before it expires
+    pub fn renew_on(&self) -> OffsetDateTime {
+        self.expire_on.saturating_sub(Duration::days(1))
+    }
+
+    /// What is wrong with the subscription at `now`, if anything
+    ///
+    /// It is stale when the hub has not pushed anything for `stale_after`, counting from
+    /// the last verification if it never did. In `poll` mode, where the hub grants no lease,
+    /// it is stale when its feed was not fetched for `stale_after`.
+    pub fn problems(
+        &self,
+        now: OffsetDateTime,
+        stale_after: Duration,
+        mode: HookMode,
+    ) -> Vec<String> {
+        if !mode.push() {
+            return match self.polled_on {
+                Some(last) if now - last > stale_after => {
+                    vec![format!("not polled since <t:{}:R>", last.unix_timestamp())]
+                }
+                _ => vec![],
+            };
+        }
+
+        let mut problems = vec![];
+        if let Some(reason) = &self.denied_reason {
+            problems.push(format!("denied by the hub: {reason}"));
         }
+        if let Some(e) = &self.renew_error {
+            problems.push(format!("renewal failed: {e}"));
+        }
+        if self.expire_on < now {
+            problems.push(format!("expired <t:{}:R>", self.expire_on.unix_timestamp()));
+        }
+        match self.notified_on.or(self.verified_on) {
+            Some(last) if now - last > stale_after => problems.push(format!(
+                "no notification since <t:{}:R>",
+                last.unix_timestamp()
+            )),
+            _ => {}
+        }
+
+        problems
+    }
+}
+
+/// Answer of the hub to a subscription request
+#[derive(Debug, Clone)]
+pub enum SubOutcome {
+    Verified { expire_on: OffsetDateTime },
+    Denied { reason: String },
+}
+
+/// Commands waiting for the hub to answer a subscription request, by yt_channel_id
+pub type PendingSubs = Arc<Mutex<HashMap<String, Vec<oneshot::Sender<SubOutcome>>>>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verified(now: OffsetDateTime) -> YtChannel {
+        YtChannel {
+            yt_channel_id: "UCXuqSBlHAE6Xw-yeJA0Tunw".to_string(),
+            yt_channel_name: "Linus Tech Tips".to_string(),
+            expire_on: now + Duration::days(4),
+            verified_on: Some(now - Duration::days(1)),
+            notified_on: Some(now - Duration::hours(2)),
+            polled_on: None,
+            denied_reason: None,
+            renew_error: None,
+            alerted_on: None,
+        }
+    }
+
+    #[test]
+    fn test_healthy_subscription() {
+        let now = OffsetDateTime::now_utc();
+        let yt_channel = verified(now);
+
+        assert!(yt_channel
+            .problems(now, Duration::days(7), HookMode::Push)
+            .is_empty());
+        assert_eq!(yt_channel.renew_on(), now + Duration::days(3));
+    }
+
+    #[test]
+    fn test_stale_subscription() {
+        let now = OffsetDateTime::now_utc();
+        let yt_channel = YtChannel {
+            notified_on: Some(now - Duration::days(8)),
+            ..verified(now)
+        };
+        assert_eq!(
+            yt_channel
+                .problems(now, Duration::days(7), HookMode::Push)
+                .len(),
+            1
+        );
+
+        // Never notified, but verified lately
+        let yt_channel = YtChannel {
+            notified_on: None,
+            ..verified(now)
+        };
+        assert!(yt_channel
+            .problems(now, Duration::days(7), HookMode::Push)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_failed_subscription() {
+        let now = OffsetDateTime::now_utc();
+        let yt_channel = YtChannel {
+            expire_on: now - Duration::hours(1),
+            renew_error: Some("hub answered 503".to_string()),
+            ..verified(now)
+        };
+
+        let problems = yt_channel.problems(now, Duration::days(7), HookMode::Push);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("renewal failed"));
+        assert!(problems[1].starts_with("expired"));
+    }
+
+    #[test]
+    fn test_polled_subscription() {
+        let now = OffsetDateTime::now_utc();
+        // No lease in poll mode
+        let yt_channel = YtChannel {
+            expire_on: now - Duration::days(30),
+            verified_on: None,
+            notified_on: None,
+            polled_on: Some(now - Duration::minutes(15)),
+            ..verified(now)
+        };
+        assert!(yt_channel
+            .problems(now, Duration::days(7), HookMode::Poll)
+            .is_empty());
+
+        let yt_channel = YtChannel {
+            polled_on: Some(now - Duration::days(8)),
+            ..yt_channel
+        };
+        let problems = yt_channel.problems(now, Duration::days(7), HookMode::Poll);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("not polled since"));
     }
 }
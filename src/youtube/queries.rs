@@ -1,28 +1,47 @@
 use time::OffsetDateTime;
 
-use super::models::{SubYtChannel, SubYtChannelSQL};
+use super::models::{SubYtChannel, SubYtChannelSQL, VideoKind, YtChannel};
 use crate::{
     database::{from_i64, to_i64, Db},
     Error,
 };
 
+/// Message id of an announcement claimed but not posted yet; no Discord id is 0
+const UNPOSTED: u64 = 0;
+
+/// Get the subscription of the guild to `yt_channel_id` posted in `post_channel_id`
 pub async fn get_sub(
     db: &Db,
-    yt_channel_name: &str,
+    yt_channel_id: &str,
     guild_id: u64,
+    post_channel_id: u64,
 ) -> Result<Option<SubYtChannel>, Error> {
     let guild_id = to_i64(guild_id);
+    let post_channel_id = to_i64(post_channel_id);
     let response = sqlx::query_as!(
         SubYtChannelSQL,
         r#"SELECT
-            yt_channel_name,
-            yt_channel_id,
-            guild_id,
-            post_channel_id,
-            expire_on
-        FROM yt_sub WHERE yt_channel_name = ? AND guild_id = ?"#,
-        yt_channel_name,
+            yt_channel.yt_channel_name,
+            yt_sub.yt_channel_id,
+            yt_sub.guild_id,
+            yt_sub.post_channel_id,
+            yt_channel.expire_on,
+            yt_channel.hub_secret,
+            yt_channel.denied_reason,
+            yt_sub.template,
+            yt_sub.mention_role_id,
+            yt_sub.announce_uploads,
+            yt_sub.announce_shorts,
+            yt_sub.announce_premieres,
+            yt_sub.announce_lives,
+            yt_sub.template_short,
+            yt_sub.template_premiere,
+            yt_sub.template_live
+        FROM yt_sub JOIN yt_channel ON yt_channel.yt_channel_id = yt_sub.yt_channel_id
+        WHERE yt_sub.yt_channel_id = ? AND yt_sub.guild_id = ? AND yt_sub.post_channel_id = ?"#,
+        yt_channel_id,
         guild_id,
+        post_channel_id
     )
     .fetch_optional(&db.pool)
     .await?;
@@ -32,31 +51,95 @@ pub async fn get_sub(
     Ok(yt_sub)
 }
 
-pub async fn get_post_channel_ids(db: &Db, yt_channel_id: &str) -> Result<Vec<u64>, Error> {
-    let response = sqlx::query!(
-        "SELECT post_channel_id FROM yt_sub WHERE yt_channel_id = ?",
+/// Get the subscriptions of the guild, every target channel included
+pub async fn get_guild_subs(db: &Db, guild_id: u64) -> Result<Vec<SubYtChannel>, Error> {
+    let guild_id = to_i64(guild_id);
+    let response = sqlx::query_as!(
+        SubYtChannelSQL,
+        r#"SELECT
+            yt_channel.yt_channel_name,
+            yt_sub.yt_channel_id,
+            yt_sub.guild_id,
+            yt_sub.post_channel_id,
+            yt_channel.expire_on,
+            yt_channel.hub_secret,
+            yt_channel.denied_reason,
+            yt_sub.template,
+            yt_sub.mention_role_id,
+            yt_sub.announce_uploads,
+            yt_sub.announce_shorts,
+            yt_sub.announce_premieres,
+            yt_sub.announce_lives,
+            yt_sub.template_short,
+            yt_sub.template_premiere,
+            yt_sub.template_live
+        FROM yt_sub JOIN yt_channel ON yt_channel.yt_channel_id = yt_sub.yt_channel_id
+        WHERE yt_sub.guild_id = ?
+        ORDER BY yt_channel.yt_channel_name, yt_sub.yt_channel_id, yt_sub.post_channel_id"#,
+        guild_id
+    )
+    .fetch_all(&db.pool)
+    .await?;
+
+    let yt_subs = response.into_iter().map(SubYtChannel::from).collect();
+
+    Ok(yt_subs)
+}
+
+/// Get the subscriptions of every guild to the channel `yt_channel_id`
+pub async fn get_subs_by_channel(db: &Db, yt_channel_id: &str) -> Result<Vec<SubYtChannel>, Error> {
+    let response = sqlx::query_as!(
+        SubYtChannelSQL,
+        r#"SELECT
+            yt_channel.yt_channel_name,
+            yt_sub.yt_channel_id,
+            yt_sub.guild_id,
+            yt_sub.post_channel_id,
+            yt_channel.expire_on,
+            yt_channel.hub_secret,
+            yt_channel.denied_reason,
+            yt_sub.template,
+            yt_sub.mention_role_id,
+            yt_sub.announce_uploads,
+            yt_sub.announce_shorts,
+            yt_sub.announce_premieres,
+            yt_sub.announce_lives,
+            yt_sub.template_short,
+            yt_sub.template_premiere,
+            yt_sub.template_live
+        FROM yt_sub JOIN yt_channel ON yt_channel.yt_channel_id = yt_sub.yt_channel_id
+        WHERE yt_sub.yt_channel_id = ?"#,
         yt_channel_id
     )
     .fetch_all(&db.pool)
     .await?;
 
-    let ids: Vec<_> = response
-        .iter()
-        .map(|r| from_i64(r.post_channel_id))
-        .collect();
-    Ok(ids)
+    let yt_subs = response.into_iter().map(SubYtChannel::from).collect();
+
+    Ok(yt_subs)
 }
 
 pub async fn get_subs_list(db: &Db) -> Result<Vec<SubYtChannel>, Error> {
     let response = sqlx::query_as!(
         SubYtChannelSQL,
         r#"SELECT
-            yt_channel_name,
-            yt_channel_id,
-            guild_id,
-            post_channel_id,
-            expire_on
-        FROM yt_sub"#
+            yt_channel.yt_channel_name,
+            yt_sub.yt_channel_id,
+            yt_sub.guild_id,
+            yt_sub.post_channel_id,
+            yt_channel.expire_on,
+            yt_channel.hub_secret,
+            yt_channel.denied_reason,
+            yt_sub.template,
+            yt_sub.mention_role_id,
+            yt_sub.announce_uploads,
+            yt_sub.announce_shorts,
+            yt_sub.announce_premieres,
+            yt_sub.announce_lives,
+            yt_sub.template_short,
+            yt_sub.template_premiere,
+            yt_sub.template_live
+        FROM yt_sub JOIN yt_channel ON yt_channel.yt_channel_id = yt_sub.yt_channel_id"#
     )
     .fetch_all(&db.pool)
     .await?;
@@ -66,19 +149,37 @@ pub async fn get_subs_list(db: &Db) -> Result<Vec<SubYtChannel>, Error> {
     Ok(yt_subs)
 }
 
+/// Store the subscription, and the Youtube channel if new
+///
+/// The expiration date of a known channel is kept until the hub verifies the new request.
 pub async fn insert_sub(db: &Db, sub: SubYtChannel) -> Result<(), Error> {
     let sub = SubYtChannelSQL::from(sub);
 
     sqlx::query!(
-        "INSERT INTO yt_sub(yt_channel_name, yt_channel_id, guild_id, post_channel_id, expire_on)
-            VALUES (?, ?, ?, ?, ?)
-        ON CONFLICT (yt_channel_id, guild_id) DO UPDATE SET yt_channel_name = ?",
+        "INSERT INTO yt_channel(yt_channel_id, yt_channel_name, expire_on, hub_secret)
+            VALUES (?, ?, ?, ?)
+        ON CONFLICT (yt_channel_id) DO UPDATE SET yt_channel_name = ?, hub_secret = COALESCE(?, hub_secret)",
+        sub.yt_channel_id,
+        sub.yt_channel_name,
+        sub.expire_on,
+        sub.hub_secret,
         sub.yt_channel_name,
+        sub.hub_secret
+    )
+    .execute(&db.pool)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO yt_sub(yt_channel_id, guild_id, post_channel_id, template, mention_role_id)
+            VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT (guild_id, yt_channel_id, post_channel_id) DO UPDATE SET template = ?, mention_role_id = ?",
         sub.yt_channel_id,
         sub.guild_id,
         sub.post_channel_id,
-        sub.expire_on,
-        sub.yt_channel_name
+        sub.template,
+        sub.mention_role_id,
+        sub.template,
+        sub.mention_role_id
     )
     .execute(&db.pool)
     .await?;
@@ -86,14 +187,95 @@ pub async fn insert_sub(db: &Db, sub: SubYtChannel) -> Result<(), Error> {
     Ok(())
 }
 
+/// Post the subscription in `to` instead of `from`
+///
+/// Returns `false` if the guild already has the channel posted in `to`.
+pub async fn move_sub(
+    db: &Db,
+    yt_channel_id: &str,
+    guild_id: u64,
+    from: u64,
+    to: u64,
+) -> Result<bool, Error> {
+    let guild_id = to_i64(guild_id);
+    let from = to_i64(from);
+    let to = to_i64(to);
+
+    let result = sqlx::query!(
+        "UPDATE OR IGNORE yt_sub SET post_channel_id = ?
+        WHERE yt_channel_id = ? AND guild_id = ? AND post_channel_id = ?",
+        to,
+        yt_channel_id,
+        guild_id,
+        from
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get every subscribed Youtube channel
+pub async fn get_yt_channels(db: &Db) -> Result<Vec<YtChannel>, Error> {
+    let response = sqlx::query_as!(
+        YtChannel,
+        r#"SELECT
+            yt_channel_id,
+            yt_channel_name,
+            expire_on,
+            verified_on,
+            notified_on,
+            polled_on,
+            denied_reason,
+            renew_error,
+            alerted_on
+        FROM yt_channel ORDER BY yt_channel_name"#
+    )
+    .fetch_all(&db.pool)
+    .await?;
+
+    Ok(response)
+}
+
+/// Get the Youtube channels the guild is subscribed to
+pub async fn get_guild_yt_channels(db: &Db, guild_id: u64) -> Result<Vec<YtChannel>, Error> {
+    let guild_id = to_i64(guild_id);
+    let response = sqlx::query_as!(
+        YtChannel,
+        r#"SELECT
+            yt_channel_id,
+            yt_channel_name,
+            expire_on,
+            verified_on,
+            notified_on,
+            polled_on,
+            denied_reason,
+            renew_error,
+            alerted_on
+        FROM yt_channel
+        WHERE yt_channel_id IN (SELECT yt_channel_id FROM yt_sub WHERE guild_id = ?)
+        ORDER BY yt_channel_name"#,
+        guild_id
+    )
+    .fetch_all(&db.pool)
+    .await?;
+
+    Ok(response)
+}
+
+/// Set the expiration date granted by the hub, which also clears a previous denial
 pub async fn update_expire_on(
     db: &Db,
     expire_on: OffsetDateTime,
     yt_channel_id: &str,
 ) -> Result<(), Error> {
+    let verified_on = OffsetDateTime::now_utc();
+
     sqlx::query!(
-        "UPDATE yt_sub SET expire_on = ? WHERE yt_channel_id = ?",
+        "UPDATE yt_channel SET expire_on = ?, verified_on = ?, denied_reason = NULL
+        WHERE yt_channel_id = ?",
         expire_on,
+        verified_on,
         yt_channel_id
     )
     .execute(&db.pool)
@@ -102,16 +284,366 @@ pub async fn update_expire_on(
     Ok(())
 }
 
-pub async fn delete_sub(db: &Db, yt_channel_id: &str, guild_id: u64) -> Result<(), Error> {
-    let guild_id = to_i64(guild_id);
+/// Record that the hub pushed a notification for the channel
+pub async fn update_notified_on(db: &Db, yt_channel_id: &str) -> Result<(), Error> {
+    let notified_on = OffsetDateTime::now_utc();
+
+    sqlx::query!(
+        "UPDATE yt_channel SET notified_on = ? WHERE yt_channel_id = ?",
+        notified_on,
+        yt_channel_id
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record that the feed of the channel was fetched
+pub async fn update_polled_on(db: &Db, yt_channel_id: &str) -> Result<(), Error> {
+    let polled_on = OffsetDateTime::now_utc();
+
+    sqlx::query!(
+        "UPDATE yt_channel SET polled_on = ? WHERE yt_channel_id = ?",
+        polled_on,
+        yt_channel_id
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Set, or clear with `None`, the error of the last renewal request
+pub async fn update_renew_error(
+    db: &Db,
+    renew_error: Option<&str>,
+    yt_channel_id: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE yt_channel SET renew_error = ? WHERE yt_channel_id = ?",
+        renew_error,
+        yt_channel_id
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn update_alerted_on(
+    db: &Db,
+    alerted_on: OffsetDateTime,
+    yt_channel_id: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE yt_channel SET alerted_on = ? WHERE yt_channel_id = ?",
+        alerted_on,
+        yt_channel_id
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the hub secret of every subscribed channel, as `(yt_channel_id, hub_secret)`
+pub async fn get_hub_secrets(db: &Db) -> Result<Vec<(String, String)>, Error> {
+    let response = sqlx::query!(
+        r#"SELECT yt_channel_id, hub_secret as "hub_secret!"
+        FROM yt_channel WHERE hub_secret IS NOT NULL"#
+    )
+    .fetch_all(&db.pool)
+    .await?;
+
+    let secrets = response
+        .into_iter()
+        .map(|r| (r.yt_channel_id, r.hub_secret))
+        .collect();
+    Ok(secrets)
+}
 
+pub async fn update_hub_secret(
+    db: &Db,
+    hub_secret: &str,
+    yt_channel_id: &str,
+) -> Result<(), Error> {
     sqlx::query!(
-        "DELETE FROM yt_sub WHERE yt_channel_id = ? AND guild_id = ?",
+        "UPDATE yt_channel SET hub_secret = ? WHERE yt_channel_id = ?",
+        hub_secret,
+        yt_channel_id
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the id of the message announcing `video_id` in `post_channel_id`
+///
+/// `None` until the message is posted, even if the announcement is claimed.
+pub async fn get_announcement(
+    db: &Db,
+    video_id: &str,
+    post_channel_id: u64,
+) -> Result<Option<u64>, Error> {
+    let post_channel_id = to_i64(post_channel_id);
+    let response = sqlx::query!(
+        "SELECT message_id FROM yt_announcement WHERE video_id = ? AND post_channel_id = ?",
+        video_id,
+        post_channel_id
+    )
+    .fetch_optional(&db.pool)
+    .await?;
+
+    Ok(response
+        .map(|r| from_i64(r.message_id))
+        .filter(|message_id| *message_id != UNPOSTED))
+}
+
+/// Reserve the announcement of `video_id` in `post_channel_id`, before posting it
+///
+/// Returns `false` if it is already announced, or being announced by the listener or the
+/// poller.
+pub async fn claim_announcement(
+    db: &Db,
+    yt_channel_id: &str,
+    video_id: &str,
+    post_channel_id: u64,
+) -> Result<bool, Error> {
+    let post_channel_id = to_i64(post_channel_id);
+    let message_id = to_i64(UNPOSTED);
+    let announced_on = OffsetDateTime::now_utc();
+
+    let result = sqlx::query!(
+        "INSERT INTO yt_announcement(yt_channel_id, video_id, post_channel_id, message_id, announced_on)
+            VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT (video_id, post_channel_id) DO NOTHING",
         yt_channel_id,
-        guild_id
+        video_id,
+        post_channel_id,
+        message_id,
+        announced_on
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Save the message posted for the announcement claimed with [`claim_announcement`]
+pub async fn set_announcement_message(
+    db: &Db,
+    video_id: &str,
+    post_channel_id: u64,
+    message_id: u64,
+) -> Result<(), Error> {
+    let post_channel_id = to_i64(post_channel_id);
+    let message_id = to_i64(message_id);
+
+    sqlx::query!(
+        "UPDATE yt_announcement SET message_id = ? WHERE video_id = ? AND post_channel_id = ?",
+        message_id,
+        video_id,
+        post_channel_id
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Release the announcement claimed with [`claim_announcement`], when it cannot be posted
+pub async fn delete_announcement(
+    db: &Db,
+    video_id: &str,
+    post_channel_id: u64,
+) -> Result<(), Error> {
+    let post_channel_id = to_i64(post_channel_id);
+
+    sqlx::query!(
+        "DELETE FROM yt_announcement WHERE video_id = ? AND post_channel_id = ?",
+        video_id,
+        post_channel_id
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the ids of the videos of `yt_channel_id` already seen, pushed or polled
+pub async fn get_seen_video_ids(db: &Db, yt_channel_id: &str) -> Result<Vec<String>, Error> {
+    let response = sqlx::query!(
+        "SELECT video_id FROM yt_video WHERE yt_channel_id = ?",
+        yt_channel_id
+    )
+    .fetch_all(&db.pool)
+    .await?;
+
+    Ok(response.into_iter().map(|r| r.video_id).collect())
+}
+
+pub async fn insert_seen_video(db: &Db, yt_channel_id: &str, video_id: &str) -> Result<(), Error> {
+    let seen_on = OffsetDateTime::now_utc();
+
+    sqlx::query!(
+        "INSERT INTO yt_video(yt_channel_id, video_id, seen_on) VALUES (?, ?, ?)
+        ON CONFLICT (yt_channel_id, video_id) DO NOTHING",
+        yt_channel_id,
+        video_id,
+        seen_on
+    )
+    .execute(&db.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Set the announcement template of the videos of `kind`
+pub async fn update_template(
+    db: &Db,
+    kind: VideoKind,
+    template: Option<&str>,
+    yt_channel_id: &str,
+    guild_id: u64,
+    post_channel_id: u64,
+) -> Result<(), Error> {
+    let guild_id = to_i64(guild_id);
+    let post_channel_id = to_i64(post_channel_id);
+
+    let query = match kind {
+        VideoKind::Upload => sqlx::query!(
+            "UPDATE yt_sub SET template = ? WHERE yt_channel_id = ? AND guild_id = ? AND post_channel_id = ?",
+            template,
+            yt_channel_id,
+            guild_id,
+            post_channel_id
+        ),
+        VideoKind::Short => sqlx::query!(
+            "UPDATE yt_sub SET template_short = ? WHERE yt_channel_id = ? AND guild_id = ? AND post_channel_id = ?",
+            template,
+            yt_channel_id,
+            guild_id,
+            post_channel_id
+        ),
+        VideoKind::Premiere => sqlx::query!(
+            "UPDATE yt_sub SET template_premiere = ? WHERE yt_channel_id = ? AND guild_id = ? AND post_channel_id = ?",
+            template,
+            yt_channel_id,
+            guild_id,
+            post_channel_id
+        ),
+        VideoKind::Live => sqlx::query!(
+            "UPDATE yt_sub SET template_live = ? WHERE yt_channel_id = ? AND guild_id = ? AND post_channel_id = ?",
+            template,
+            yt_channel_id,
+            guild_id,
+            post_channel_id
+        ),
+    };
+    query.execute(&db.pool).await?;
+
+    Ok(())
+}
+
+/// Turn on or off the announcement of the videos of `kind`
+pub async fn update_announce(
+    db: &Db,
+    kind: VideoKind,
+    announce: bool,
+    yt_channel_id: &str,
+    guild_id: u64,
+    post_channel_id: u64,
+) -> Result<(), Error> {
+    let guild_id = to_i64(guild_id);
+    let post_channel_id = to_i64(post_channel_id);
+
+    let query = match kind {
+        VideoKind::Upload => sqlx::query!(
+            "UPDATE yt_sub SET announce_uploads = ? WHERE yt_channel_id = ? AND guild_id = ? AND post_channel_id = ?",
+            announce,
+            yt_channel_id,
+            guild_id,
+            post_channel_id
+        ),
+        VideoKind::Short => sqlx::query!(
+            "UPDATE yt_sub SET announce_shorts = ? WHERE yt_channel_id = ? AND guild_id = ? AND post_channel_id = ?",
+            announce,
+            yt_channel_id,
+            guild_id,
+            post_channel_id
+        ),
+        VideoKind::Premiere => sqlx::query!(
+            "UPDATE yt_sub SET announce_premieres = ? WHERE yt_channel_id = ? AND guild_id = ? AND post_channel_id = ?",
+            announce,
+            yt_channel_id,
+            guild_id,
+            post_channel_id
+        ),
+        VideoKind::Live => sqlx::query!(
+            "UPDATE yt_sub SET announce_lives = ? WHERE yt_channel_id = ? AND guild_id = ? AND post_channel_id = ?",
+            announce,
+            yt_channel_id,
+            guild_id,
+            post_channel_id
+        ),
+    };
+    query.execute(&db.pool).await?;
+
+    Ok(())
+}
+
+pub async fn set_denied(db: &Db, reason: &str, yt_channel_id: &str) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE yt_channel SET denied_reason = ? WHERE yt_channel_id = ?",
+        reason,
+        yt_channel_id
     )
     .execute(&db.pool)
     .await?;
 
     Ok(())
 }
+
+/// Delete the subscription of the guild posted in `post_channel_id`
+///
+/// Returns the number of subscriptions left to the channel, in every guild; the channel
+/// itself is deleted with the last one.
+pub async fn delete_sub(
+    db: &Db,
+    yt_channel_id: &str,
+    guild_id: u64,
+    post_channel_id: u64,
+) -> Result<i64, Error> {
+    let guild_id = to_i64(guild_id);
+    let post_channel_id = to_i64(post_channel_id);
+
+    sqlx::query!(
+        "DELETE FROM yt_sub WHERE yt_channel_id = ? AND guild_id = ? AND post_channel_id = ?",
+        yt_channel_id,
+        guild_id,
+        post_channel_id
+    )
+    .execute(&db.pool)
+    .await?;
+
+    let left = sqlx::query!(
+        r#"SELECT COUNT(*) as "count: i64" FROM yt_sub WHERE yt_channel_id = ?"#,
+        yt_channel_id
+    )
+    .fetch_one(&db.pool)
+    .await?
+    .count;
+
+    if left == 0 {
+        sqlx::query!(
+            "DELETE FROM yt_channel WHERE yt_channel_id = ?",
+            yt_channel_id
+        )
+        .execute(&db.pool)
+        .await?;
+    }
+
+    Ok(left)
+}
@@ -0,0 +1,239 @@
+use tracing::{debug, warn};
+use url::Url;
+
+use super::invidious::{Channel, InvidiousClient, SearchItem, SearchKind};
+use crate::Error;
+
+const YOUTUBE_HOSTS: [&str; 4] = [
+    "youtube.com",
+    "www.youtube.com",
+    "m.youtube.com",
+    "music.youtube.com",
+];
+const SHORT_HOST: &str = "youtu.be";
+
+/// First path segments of the YouTube pages that are not channels
+const NOT_CHANNELS: [&str; 6] = [
+    "feed", "results", "playlist", "hashtag", "account", "premium",
+];
+
+/// What a YouTube address given by a user points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelRef {
+    /// `UC...` channel id, from `/channel/UC...`
+    Id(String),
+    /// `@handle`
+    Handle(String),
+    /// Legacy custom name, from `/c/Name` or `/Name`
+    Custom(String),
+    /// Legacy username, from `/user/Name`
+    User(String),
+    /// A video, resolved to its uploader
+    Video(String),
+}
+
+impl ChannelRef {
+    /// Canonical address of a named channel
+    fn url(&self) -> Option<String> {
+        match self {
+            Self::Handle(handle) => Some(format!("https://www.youtube.com/@{handle}")),
+            Self::Custom(name) => Some(format!("https://www.youtube.com/c/{name}")),
+            Self::User(name) => Some(format!("https://www.youtube.com/user/{name}")),
+            Self::Id(_) | Self::Video(_) => None,
+        }
+    }
+}
+
+/// Read the channel or video from any common form of YouTube address
+///
+/// The scheme, the `www.` and the query parameters like `?si=` are optional, a bare
+/// channel id or `@handle` is accepted too.
+pub fn parse(input: &str) -> Option<ChannelRef> {
+    let input = input.trim();
+    if let Some(handle) = input.strip_prefix('@') {
+        return valid(handle).then(|| ChannelRef::Handle(handle.to_string()));
+    }
+    if is_channel_id(input) {
+        return Some(ChannelRef::Id(input.to_string()));
+    }
+
+    let url = if input.contains("://") {
+        Url::parse(input)
+    } else {
+        Url::parse(&format!("https://{input}"))
+    }
+    .ok()?;
+    let host = url.host_str()?.to_lowercase();
+    let segments = url
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    if host == SHORT_HOST {
+        let video_id = segments.first()?;
+        return valid(video_id).then(|| ChannelRef::Video((*video_id).to_string()));
+    }
+    if !YOUTUBE_HOSTS.contains(&host.as_str()) {
+        return None;
+    }
+
+    let channel_ref = match segments.as_slice() {
+        ["watch", ..] => {
+            let (_, video_id) = url.query_pairs().find(|(key, _)| key == "v")?;
+            ChannelRef::Video(video_id.into_owned())
+        }
+        ["shorts" | "live" | "embed" | "v", video_id, ..] => {
+            ChannelRef::Video((*video_id).to_string())
+        }
+        ["channel", id, ..] if is_channel_id(id) => ChannelRef::Id((*id).to_string()),
+        ["c", name, ..] => ChannelRef::Custom(decode(name)),
+        ["user", name, ..] => ChannelRef::User(decode(name)),
+        [first, ..] if first.starts_with('@') => ChannelRef::Handle(decode(&first[1..])),
+        [name, ..] if !NOT_CHANNELS.contains(name) => ChannelRef::Custom(decode(name)),
+        _ => return None,
+    };
+
+    match &channel_ref {
+        ChannelRef::Id(value)
+        | ChannelRef::Handle(value)
+        | ChannelRef::Custom(value)
+        | ChannelRef::User(value)
+        | ChannelRef::Video(value) => valid(value).then_some(channel_ref),
+    }
+}
+
+/// Find the channel `channel_ref` points to
+///
+/// Named channels are resolved by Invidious; if it cannot, only a search result with the
+/// exact same name is trusted.
+pub async fn resolve(
+    invidious: &InvidiousClient,
+    channel_ref: &ChannelRef,
+) -> Result<Option<Channel>, Error> {
+    let channel_id = match channel_ref {
+        ChannelRef::Id(id) => id.clone(),
+        ChannelRef::Video(video_id) => invidious.video(video_id).await?.author_id,
+        ChannelRef::Handle(name) | ChannelRef::Custom(name) | ChannelRef::User(name) => {
+            let url = channel_ref.url().unwrap_or_default();
+            match invidious.resolve_url(&url).await {
+                Ok(resolved) => match resolved.ucid {
+                    Some(ucid) => ucid,
+                    None => {
+                        debug!("{url} is not a channel: {:?}", resolved.page_type);
+                        return Ok(None);
+                    }
+                },
+                Err(e) => {
+                    warn!("Cannot resolve {url}: {e}");
+                    match search_exact(invidious, channel_ref, name).await? {
+                        Some(channel_id) => channel_id,
+                        None => return Ok(None),
+                    }
+                }
+            }
+        }
+    };
+
+    Ok(Some(invidious.channel(&channel_id).await?))
+}
+
+/// Id of the channel searched by `name` whose handle or name is `name`
+async fn search_exact(
+    invidious: &InvidiousClient,
+    channel_ref: &ChannelRef,
+    name: &str,
+) -> Result<Option<String>, Error> {
+    let found = invidious
+        .search(name, SearchKind::Channel, None)
+        .await?
+        .into_iter()
+        .find_map(|item| match item {
+            SearchItem::Channel(channel) => {
+                let matches = match channel_ref {
+                    ChannelRef::Handle(_) => {
+                        channel.channel_handle.as_deref().is_some_and(|handle| {
+                            handle.trim_start_matches('@').eq_ignore_ascii_case(name)
+                        })
+                    }
+                    _ => channel.author.eq_ignore_ascii_case(name),
+                };
+                matches.then_some(channel.author_id)
+            }
+            _ => None,
+        });
+    Ok(found)
+}
+
+fn is_channel_id(value: &str) -> bool {
+    value.len() == 24 && value.starts_with("UC") && valid(value)
+}
+
+/// Ids and names cannot break out of the path of the requests
+fn valid(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with('.')
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn decode(segment: &str) -> String {
+    percent_encoding::percent_decode_str(segment)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "UCXuqSBlHAE6Xw-yeJA0Tunw";
+
+    #[test]
+    fn test_parse_channels() {
+        let id = Some(ChannelRef::Id(ID.to_string()));
+        assert_eq!(parse(ID), id);
+        assert_eq!(parse(&format!("https://www.youtube.com/channel/{ID}")), id);
+        assert_eq!(parse(&format!("youtube.com/channel/{ID}/videos?si=x")), id);
+        assert_eq!(parse(&format!("https://m.youtube.com/channel/{ID}")), id);
+
+        let handle = Some(ChannelRef::Handle("LinusTechTips".to_string()));
+        assert_eq!(parse("@LinusTechTips"), handle);
+        assert_eq!(parse("https://www.youtube.com/@LinusTechTips"), handle);
+        assert_eq!(parse("www.youtube.com/@LinusTechTips/videos?si=x"), handle);
+
+        assert_eq!(
+            parse("https://www.youtube.com/c/LinusTechTips"),
+            Some(ChannelRef::Custom("LinusTechTips".to_string()))
+        );
+        assert_eq!(
+            parse("https://www.youtube.com/LinusTechTips"),
+            Some(ChannelRef::Custom("LinusTechTips".to_string()))
+        );
+        assert_eq!(
+            parse("http://youtube.com/user/LinusTechTips"),
+            Some(ChannelRef::User("LinusTechTips".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_videos() {
+        let video = Some(ChannelRef::Video("dQw4w9WgXcQ".to_string()));
+        assert_eq!(parse("https://www.youtube.com/watch?v=dQw4w9WgXcQ"), video);
+        assert_eq!(parse("https://youtube.com/watch?si=x&v=dQw4w9WgXcQ"), video);
+        assert_eq!(parse("https://youtu.be/dQw4w9WgXcQ?si=x"), video);
+        assert_eq!(parse("https://www.youtube.com/shorts/dQw4w9WgXcQ"), video);
+        assert_eq!(parse("https://www.youtube.com/live/dQw4w9WgXcQ"), video);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(parse("LinusTechTips"), None);
+        assert_eq!(parse("https://example.com/@LinusTechTips"), None);
+        assert_eq!(parse("https://www.youtube.com/"), None);
+        assert_eq!(parse("https://www.youtube.com/feed/trending"), None);
+        assert_eq!(parse("https://www.youtube.com/watch?list=PL1"), None);
+        assert_eq!(parse("https://www.youtube.com/c/..%2Fvideos%3Fx"), None);
+        assert_eq!(parse("https://youtu.be/%2E%2E"), None);
+    }
+}
@@ -0,0 +1,157 @@
+use time::OffsetDateTime;
+
+use super::constants::DEFAULT_TEMPLATE;
+
+const PLACEHOLDERS: [&str; 5] = ["channel", "title", "url", "published", "role"];
+
+/// Values of the placeholders of an announcement template
+#[derive(Debug)]
+pub struct Announcement<'a> {
+    pub channel: &'a str,
+    pub title: &'a str,
+    pub url: &'a str,
+    pub published: OffsetDateTime,
+    pub role: Option<String>,
+}
+
+/// Check that a template given by a user only has known placeholders,
+/// and turn the `\n` typed in slash commands into new lines
+pub fn parse(template: &str) -> Result<String, String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err("Unclosed `{` in template".to_string());
+        };
+        let name = &rest[start + 1..start + end];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "Unknown placeholder `{{{name}}}`, expected one of: {}",
+                PLACEHOLDERS.map(|p| format!("`{{{p}}}`")).join(", ")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    Ok(template.replace("\\n", "\n"))
+}
+
+/// Fill the placeholders of `template`, or of the default template if `None`
+///
+/// The role is put first if the template has no `{role}`.
+pub fn render(template: Option<&str>, announcement: &Announcement) -> String {
+    // Discord shows the timestamp in the time zone and language of each user
+    let published = format!("<t:{}:f>", announcement.published.unix_timestamp());
+
+    let template = template.unwrap_or(DEFAULT_TEMPLATE);
+    let template = match &announcement.role {
+        Some(_) if !template.contains("{role}") => format!("{{role}} {template}"),
+        _ => template.to_string(),
+    };
+
+    let role = announcement.role.as_deref().unwrap_or_default();
+    let value = |name: &str| match name {
+        "channel" => Some(announcement.channel),
+        "title" => Some(announcement.title),
+        "url" => Some(announcement.url),
+        "published" => Some(published.as_str()),
+        "role" => Some(role),
+        _ => None,
+    };
+
+    // In a single pass, so that the placeholders typed in a title or a channel name are
+    // left as is
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template.as_str();
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let placeholder = rest
+            .find('}')
+            .and_then(|end| Some((value(&rest[1..end])?, end)));
+        match placeholder {
+            Some((value, end)) => {
+                rendered.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+
+    rendered.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announcement() -> Announcement<'static> {
+        Announcement {
+            channel: "Linus Tech Tips",
+            title: "Tips & Tricks",
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            published: OffsetDateTime::from_unix_timestamp(1_709_229_609).unwrap(),
+            role: None,
+        }
+    }
+
+    #[test]
+    fn default_template() {
+        assert_eq!(
+            render(None, &announcement()),
+            "New video from **Linus Tech Tips** !!!\nhttps://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+    }
+
+    #[test]
+    fn custom_template() {
+        let template =
+            parse(r"{role} Nouvelle vidéo de {channel} : {title}\n{url} ({published})").unwrap();
+
+        assert_eq!(
+            render(Some(&template), &announcement()),
+            "Nouvelle vidéo de Linus Tech Tips : Tips & Tricks\nhttps://www.youtube.com/watch?v=dQw4w9WgXcQ (<t:1709229609:f>)"
+        );
+    }
+
+    #[test]
+    fn role_is_mentioned() {
+        let announcement = Announcement {
+            role: Some("<@&1234>".to_string()),
+            ..announcement()
+        };
+
+        assert_eq!(
+            render(Some("{channel}: {url}"), &announcement),
+            "<@&1234> Linus Tech Tips: https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+        assert_eq!(
+            render(Some("{url} {role}"), &announcement),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ <@&1234>"
+        );
+    }
+
+    #[test]
+    fn placeholders_in_values_are_kept() {
+        let announcement = Announcement {
+            channel: "{title}",
+            title: "Free {role} for {url}",
+            role: Some("<@&1234>".to_string()),
+            ..announcement()
+        };
+
+        assert_eq!(
+            render(Some("{role} {channel}: {title} {unknown}"), &announcement),
+            "<@&1234> {title}: Free {role} for {url} {unknown}"
+        );
+    }
+
+    #[test]
+    fn invalid_template() {
+        assert!(parse("{channel} {author}").is_err());
+        assert!(parse("{channel").is_err());
+    }
+}